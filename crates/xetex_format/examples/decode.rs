@@ -22,6 +22,7 @@ impl Options {
             Commands::Catcodes(c) => c.execute_catcodes(),
             Commands::ControlSequences(c) => c.execute(),
             Commands::Strings(c) => c.execute_strings(),
+            Commands::TokenParameters(c) => c.execute_toks_parameters(),
         }
     }
 }
@@ -43,6 +44,10 @@ enum Commands {
     #[structopt(name = "strings")]
     /// Dump the strings table
     Strings(GenericCommand),
+
+    #[structopt(name = "toks")]
+    /// Dump the token-list parameters
+    TokenParameters(GenericCommand),
 }
 
 #[derive(Debug, PartialEq, StructOpt)]
@@ -83,6 +88,14 @@ impl GenericCommand {
         fmt.dump_string_table(&mut lock)?;
         Ok(())
     }
+
+    fn execute_toks_parameters(self) -> Result<()> {
+        let fmt = self.parse()?;
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        fmt.dump_toks_parameters(&mut lock)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, PartialEq, StructOpt)]
@@ -16,6 +16,10 @@ pub const NUMBER_USVS: usize = 0x11_0000;
 /// The number of basic TeX register.
 pub const NUMBER_REGS: usize = 256;
 
+/// The largest register number. Registers numbered `NUMBER_REGS` and above are
+/// stored in the e-TeX sparse arrays in `mem`.
+pub const MAX_REG_NUM: usize = 32767;
+
 /// The number of TeX math fonts.
 pub const NUMBER_MATH_FONTS: usize = 3 * 256;
 
@@ -72,6 +72,43 @@ impl CatCode {
         }
     }
 
+    /// Get the category code that a character has in a freshly initialized
+    /// engine, before any format setup.
+    pub fn initial_for(c: i32) -> Self {
+        match c {
+            0 => CatCode::Ignored,
+            13 => CatCode::CarriageReturn,
+            32 => CatCode::Space,
+            37 => CatCode::Comment,
+            92 => CatCode::Escape,
+            127 => CatCode::Invalid,
+            0x41..=0x5A | 0x61..=0x7A => CatCode::Letter,
+            _ => CatCode::Other,
+        }
+    }
+
+    /// Get the special category code that plain TeX and LaTeX conventionally
+    /// assign to a character, if any.
+    ///
+    /// These are the characters listed in `\dospecials`, which verbatim
+    /// environments reset to [`CatCode::Other`].
+    pub fn conventional_special(c: i32) -> Option<Self> {
+        Some(match c {
+            0x20 => CatCode::Space,
+            0x5C => CatCode::Escape,
+            0x7B => CatCode::BeginGroup,
+            0x7D => CatCode::EndGroup,
+            0x24 => CatCode::MathShift,
+            0x26 => CatCode::Tab,
+            0x23 => CatCode::MacroParam,
+            0x5E => CatCode::Superscript,
+            0x5F => CatCode::Subscript,
+            0x25 => CatCode::Comment,
+            0x7E => CatCode::Active,
+            _ => return None,
+        })
+    }
+
     pub fn from_i32(n: i32) -> Result<Self> {
         match n {
             0 => Ok(CatCode::Escape),
@@ -105,3 +142,44 @@ impl fmt::Display for CatCode {
         write!(f, "{}", self.abbrev())
     }
 }
+
+/// The category-code assignments in a format that are characteristic of
+/// verbatim and listing machinery.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct VerbatimCatcodes {
+    /// Characters that conventionally have a special category code, but
+    /// that have been made [`CatCode::Other`]. Each is given along with its
+    /// conventional category code.
+    pub othered_specials: Vec<(char, CatCode)>,
+
+    /// Characters that have been made [`CatCode::Active`], other than `~`,
+    /// which is conventionally active anyway.
+    pub actives: Vec<char>,
+}
+
+impl VerbatimCatcodes {
+    /// Return true if no verbatim-style assignments were found.
+    pub fn is_empty(&self) -> bool {
+        self.othered_specials.is_empty() && self.actives.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventional_specials() {
+        assert_eq!(CatCode::conventional_special(0x5C), Some(CatCode::Escape));
+        assert_eq!(CatCode::conventional_special(0x7E), Some(CatCode::Active));
+        assert_eq!(CatCode::conventional_special(0x41), None);
+
+        // Where IniTeX and plain TeX agree, the conventions should match.
+        for c in [0x20, 0x25, 0x5C].iter() {
+            assert_eq!(
+                CatCode::conventional_special(*c),
+                Some(CatCode::initial_for(*c))
+            );
+        }
+    }
+}
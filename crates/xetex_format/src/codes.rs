@@ -0,0 +1,78 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! The per-character code tables: `\lccode`, `\uccode`, `\sfcode`, and
+//! `\delcode`.
+//!
+//! The category codes and math codes have their own modules, [`crate::catcodes`]
+//! and [`crate::mathcodes`].
+
+/// Get the `\lccode` that a character has in a freshly initialized engine.
+///
+/// The Latin letters map to their lowercase forms, and everything else to
+/// zero.
+pub fn initial_lc_code(c: i32) -> i32 {
+    match c {
+        0x41..=0x5A => c + 0x20,
+        0x61..=0x7A => c,
+        _ => 0,
+    }
+}
+
+/// Get the `\uccode` that a character has in a freshly initialized engine.
+///
+/// The Latin letters map to their uppercase forms, and everything else to
+/// zero.
+pub fn initial_uc_code(c: i32) -> i32 {
+    match c {
+        0x41..=0x5A => c,
+        0x61..=0x7A => c - 0x20,
+        _ => 0,
+    }
+}
+
+/// Get the `\sfcode` that a character has in a freshly initialized engine.
+///
+/// This is 999 for the uppercase Latin letters, so that a period after
+/// them doesn't end a sentence, and 1000 for everything else.
+pub fn initial_sf_code(c: i32) -> i32 {
+    match c {
+        0x41..=0x5A => 999,
+        _ => 1000,
+    }
+}
+
+/// Get the `\delcode` that a character has in a freshly initialized engine.
+///
+/// This is -1, meaning "not a delimiter", for everything except the period,
+/// which is the null delimiter.
+pub fn initial_del_code(c: i32) -> i32 {
+    match c {
+        0x2E => 0,
+        _ => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_codes() {
+        assert_eq!(initial_lc_code('A' as i32), 'a' as i32);
+        assert_eq!(initial_uc_code('a' as i32), 'A' as i32);
+        assert_eq!(initial_lc_code('0' as i32), 0);
+        assert_eq!(initial_sf_code('A' as i32), 999);
+        assert_eq!(initial_sf_code('a' as i32), 1000);
+        assert_eq!(initial_del_code('.' as i32), 0);
+        assert_eq!(initial_del_code('(' as i32), -1);
+    }
+
+    #[test]
+    fn uppercase_sf_codes() {
+        for c in 'A'..='Z' {
+            assert_eq!(initial_sf_code(c as i32), 999);
+            assert_eq!(initial_sf_code(c.to_ascii_lowercase() as i32), 1000);
+        }
+    }
+}
@@ -105,7 +105,7 @@ struct CommandMetadata {
     group: CommandGroup,
 }
 
-trait Command: HasSymbol + std::fmt::Debug {
+trait Command: HasSymbol + std::fmt::Debug + Send + Sync {
     fn describe(&self, _arg: CommandArgument) -> String;
     fn primitives(&self) -> Vec<CommandPrimitive>;
 
@@ -385,6 +385,26 @@ impl Commands {
         }
     }
 
+    /// Get the primitives associated with the commands, as tuples of
+    /// `(name, code, arg)`.
+    ///
+    /// This does not include the primitives associated with the various
+    /// parameter tables.
+    pub fn primitives(
+        &self,
+        symbols: &SymbolTable,
+    ) -> Vec<(&'static str, CommandCode, CommandArgument)> {
+        let mut prims = Vec::new();
+
+        for (code, cmd) in &self.codes {
+            for prim in cmd.primitives() {
+                prims.push((prim.name, *code, prim.arg.get_value(symbols)));
+            }
+        }
+
+        prims
+    }
+
     /// Emit the beginning of the C header information for the commands and
     /// primitives.
     pub fn emit_c_header_beginning<W: Write>(&self, mut stream: W) -> Result<()> {
@@ -6,7 +6,7 @@
 //! Commands whose arguments come from the symbol table, but not one of the
 //! enumerations.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use tectonic_errors::prelude::*;
 
 use super::{
@@ -25,12 +25,12 @@ macro_rules! declare {
     ($typename:tt { $($primname:ident $symname:ident [$($offset:tt)+] [$($init:tt)+],)+ } ) => {
         #[derive(Debug)]
         pub struct $typename {
-            args: HashMap<CommandArgument, String>,
+            args: BTreeMap<CommandArgument, String>,
         }
 
         impl CommandBehavior for $typename {
             fn build(_version: FormatVersion, symbols: &mut SymbolTable) -> Result<Self> {
-                let mut args = HashMap::new();
+                let mut args = BTreeMap::new();
 
                 $(
                     let offset = Offset::$($offset)+;
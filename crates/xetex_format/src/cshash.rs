@@ -3,18 +3,14 @@
 
 //! The hash table for multi-letter control sequences.
 
-use nom::{
-    multi::count,
-    number::complete::{be_i32, be_u8},
-    IResult,
-};
+use nom::{bytes::complete::take, number::complete::be_i32};
 use tectonic_errors::prelude::*;
 
 use crate::{
     base::{self, SIZEOF_MEMORY_WORD},
     engine::Engine,
     eqtb::EqtbPointer,
-    parseutils,
+    parseutils::{self, ParseResult},
     stringtable::{StrPointer, StringTable},
     symbols::{SymbolCategory, SymbolTable},
 };
@@ -38,6 +34,7 @@ pub struct ControlSeqHash {
     hash_offset: i32,
     single_base: EqtbPointer,
     null_cs_loc: EqtbPointer,
+    frozen_cs_loc: EqtbPointer,
     undefined_cs_loc: EqtbPointer,
     eqtb_size: EqtbPointer,
     eqtb_top: EqtbPointer,
@@ -46,16 +43,19 @@ pub struct ControlSeqHash {
 }
 
 impl ControlSeqHash {
+    /// Parse the hash table, storing it in *buf*.
     pub(crate) fn parse<'a>(
         input: &'a [u8],
         engine: &Engine,
         hash_high: i32,
-    ) -> IResult<&'a [u8], Self> {
+        buf: Vec<u8>,
+    ) -> ParseResult<'a, Self> {
         let hash_base = engine.symbols.lookup("HASH_BASE") as EqtbPointer;
         let hash_prime = engine.symbols.lookup("HASH_PRIME") as u32;
         let hash_offset = engine.symbols.lookup("HASH_OFFSET") as i32;
         let single_base = engine.symbols.lookup("SINGLE_BASE") as EqtbPointer;
         let null_cs_loc = engine.symbols.lookup("NULL_CS") as EqtbPointer;
+        let frozen_cs_loc = engine.symbols.lookup("FROZEN_CONTROL_SEQUENCE") as EqtbPointer;
         let undefined_cs_loc = engine.symbols.lookup("UNDEFINED_CONTROL_SEQUENCE") as EqtbPointer;
         let eqtb_size = engine.symbols.lookup("EQTB_SIZE") as EqtbPointer;
         let eqtb_top = engine.symbols.lookup("EQTB_TOP") as EqtbPointer;
@@ -65,12 +65,10 @@ impl ControlSeqHash {
         let index = |i: i32| (i - hash_offset) as usize * SIZEOF_MEMORY_WORD;
 
         let high_hash_size = eqtb_top + 1 - hash_offset;
-        let mut need_offset_hash = vec![0u8; high_hash_size as usize * SIZEOF_MEMORY_WORD];
+        let mut need_offset_hash =
+            parseutils::zeroed_buffer(buf, high_hash_size as usize * SIZEOF_MEMORY_WORD);
 
-        let (input, hash_used) = parseutils::ranged_be_i32(
-            hash_base,
-            engine.symbols.lookup("FROZEN_CONTROL_SEQUENCE") as i32,
-        )(input)?;
+        let (input, hash_used) = parseutils::ranged_be_i32(hash_base, frozen_cs_loc)(input)?;
 
         let mut p = hash_base - 1;
         let mut input = input;
@@ -80,9 +78,9 @@ impl ControlSeqHash {
             p = new_p;
 
             // TODO: load directly into `hash`?
-            let (ii, block) = count(be_u8, 8)(ii)?;
+            let (ii, block) = take(8usize)(ii)?;
             let ofs = index(p);
-            need_offset_hash[ofs..ofs + 8].copy_from_slice(&block[..]);
+            need_offset_hash[ofs..ofs + 8].copy_from_slice(block);
 
             input = ii;
 
@@ -95,18 +93,18 @@ impl ControlSeqHash {
         let nb = ((engine.symbols.lookup("UNDEFINED_CONTROL_SEQUENCE") as i32 - 1) - hash_used)
             as usize
             * SIZEOF_MEMORY_WORD;
-        let (input, block) = count(be_u8, nb)(input)?;
+        let (input, block) = take(nb)(input)?;
         let ofs = index(hash_used + 1);
-        need_offset_hash[ofs..ofs + nb].copy_from_slice(&block[..]);
+        need_offset_hash[ofs..ofs + nb].copy_from_slice(block);
 
         let mut input = input;
 
         if hash_high > 0 {
             let nb = hash_high as usize * SIZEOF_MEMORY_WORD;
-            let (new_input, block) = count(be_u8, nb)(input)?;
+            let (new_input, block) = take(nb)(input)?;
             input = new_input;
             let ofs = index(eqtb_size + 1);
-            need_offset_hash[ofs..ofs + nb].copy_from_slice(&block[..]);
+            need_offset_hash[ofs..ofs + nb].copy_from_slice(block);
         }
 
         let (input, _cs_count) = be_i32(input)?;
@@ -120,6 +118,7 @@ impl ControlSeqHash {
                 hash_offset,
                 single_base,
                 null_cs_loc,
+                frozen_cs_loc,
                 undefined_cs_loc,
                 eqtb_size,
                 eqtb_top,
@@ -129,6 +128,46 @@ impl ControlSeqHash {
         ))
     }
 
+    /// Skip over the control-sequence hash table without decoding it.
+    pub(crate) fn skip<'a>(
+        input: &'a [u8],
+        engine: &Engine,
+        hash_high: i32,
+    ) -> ParseResult<'a, ()> {
+        let hash_base = engine.symbols.lookup("HASH_BASE") as EqtbPointer;
+        let undefined_cs_loc = engine.symbols.lookup("UNDEFINED_CONTROL_SEQUENCE") as EqtbPointer;
+
+        let (input, hash_used) = parseutils::ranged_be_i32(
+            hash_base,
+            engine.symbols.lookup("FROZEN_CONTROL_SEQUENCE") as i32,
+        )(input)?;
+
+        let mut p = hash_base - 1;
+        let mut input = input;
+
+        loop {
+            let (ii, new_p) = parseutils::ranged_be_i32(p + 1, hash_used)(input)?;
+            p = new_p;
+            let (ii, _) = take(SIZEOF_MEMORY_WORD)(ii)?;
+            input = ii;
+
+            if p == hash_used {
+                break;
+            }
+        }
+
+        let nb = ((undefined_cs_loc - 1) - hash_used) as usize * SIZEOF_MEMORY_WORD;
+        let (mut input, _) = take(nb)(input)?;
+
+        if hash_high > 0 {
+            let (ii, _) = take(hash_high as usize * SIZEOF_MEMORY_WORD)(input)?;
+            input = ii;
+        }
+
+        let (input, _cs_count) = be_i32(input)?;
+        Ok((input, ()))
+    }
+
     fn decode(&self, index: i32) -> (StrPointer, i32) {
         let index = index - self.hash_offset;
         let text_ptr = base::memword_read_b32_s1(&self.need_offset_hash[..], index);
@@ -136,6 +175,74 @@ impl ControlSeqHash {
         (text_ptr, next_ptr)
     }
 
+    /// Get the number of buckets in the hash table.
+    ///
+    /// This is the hash prime: control-sequence names are hashed to a number
+    /// below it, which selects the bucket's first slot.
+    pub fn bucket_count(&self) -> usize {
+        self.hash_prime as usize
+    }
+
+    /// Get the chain of entries in hash bucket *h*.
+    ///
+    /// Each entry gives the string pointer of a control sequence's name and
+    /// its location in the eqtb, in chain order. Buckets whose first slot is
+    /// unused are empty, as are nonexistent buckets.
+    pub fn bucket(&self, h: usize) -> Vec<(StrPointer, EqtbPointer)> {
+        let mut chain = Vec::new();
+
+        if h >= self.bucket_count() {
+            return chain;
+        }
+
+        let mut p = h as i32 + self.hash_base;
+
+        // Bound the walk in case the links are corrupt.
+        for _ in self.hash_base..=self.eqtb_top {
+            let (str_ptr, next_ptr) = self.decode(p);
+
+            if str_ptr > 0 {
+                chain.push((str_ptr, p));
+            }
+
+            if next_ptr == 0 || next_ptr < self.hash_offset || next_ptr > self.eqtb_top {
+                break;
+            }
+
+            p = next_ptr;
+        }
+
+        chain
+    }
+
+    /// Get the number of slots in which the names of new control sequences
+    /// can be stored.
+    ///
+    /// This is the size of the main hash region, below the frozen control
+    /// sequences, plus the size of the extra region above the eqtb.
+    pub fn slot_count(&self) -> usize {
+        (self.frozen_cs_loc - self.hash_base) as usize + (self.eqtb_top - self.eqtb_size) as usize
+    }
+
+    /// Get the number of slots counted by [`ControlSeqHash::slot_count`] that
+    /// hold names.
+    pub fn occupied_slots(&self) -> usize {
+        (self.hash_base..self.frozen_cs_loc)
+            .chain(self.eqtb_size + 1..=self.eqtb_top)
+            .filter(|p| self.decode(*p).0 > 0)
+            .count()
+    }
+
+    /// Get the raw memory words of the table.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.need_offset_hash[..]
+    }
+
+    /// Give up the table's storage so that it can be reused.
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        self.need_offset_hash
+    }
+
     pub fn lookup(&self, csname: &str, strings: &StringTable) -> Option<EqtbPointer> {
         let csname_len_utf16 = crate::stringtable::len_utf16(csname);
 
@@ -214,3 +321,42 @@ impl ControlSeqHash {
         Some(strings.lookup(text_ptr).to_owned())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket() {
+        let mut hash = ControlSeqHash {
+            need_offset_hash: vec![0; 40 * SIZEOF_MEMORY_WORD],
+            hash_base: 10,
+            hash_prime: 7,
+            hash_offset: 0,
+            single_base: 5,
+            null_cs_loc: 9,
+            frozen_cs_loc: 20,
+            undefined_cs_loc: 30,
+            eqtb_size: 35,
+            eqtb_top: 39,
+            prim_eqtb_base: 25,
+            frozen_null_font_loc: 28,
+        };
+
+        // Bucket 2 holds two names, chained from slot 12 to slot 24.
+        base::memword_write_b32_s1(&mut hash.need_offset_hash[..], 12, 0x10000);
+        base::memword_write_b32_s0(&mut hash.need_offset_hash[..], 12, 24);
+        base::memword_write_b32_s1(&mut hash.need_offset_hash[..], 24, 0x10001);
+
+        assert_eq!(hash.bucket_count(), 7);
+        assert_eq!(hash.bucket(2), vec![(0x10000, 12), (0x10001, 24)]);
+        assert!(hash.bucket(3).is_empty());
+        assert!(hash.bucket(7).is_empty());
+
+        // Slot 24 is in the frozen region, so it isn't counted; slot 37 is in
+        // the extra region, so it is.
+        base::memword_write_b32_s1(&mut hash.need_offset_hash[..], 37, 0x10002);
+        assert_eq!(hash.slot_count(), 14);
+        assert_eq!(hash.occupied_slots(), 2);
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Summaries of the differences between two formats.
+
+use std::{collections::BTreeMap, fmt};
+
+/// A category of differences between formats.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DiffCategory {
+    /// Strings present in one format's string table but not the other's.
+    Strings,
+
+    /// Integer parameters with different values.
+    IntParameters,
+
+    /// Dimension parameters with different values.
+    DimenParameters,
+
+    /// Characters with different category codes.
+    Catcodes,
+
+    /// Differences in the preloaded fonts.
+    Fonts,
+}
+
+impl fmt::Display for DiffCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DiffCategory::Strings => "strings",
+            DiffCategory::IntParameters => "integer parameters",
+            DiffCategory::DimenParameters => "dimension parameters",
+            DiffCategory::Catcodes => "category codes",
+            DiffCategory::Fonts => "fonts",
+        })
+    }
+}
+
+/// A single difference between two formats.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Difference {
+    /// The category of the difference.
+    pub category: DiffCategory,
+
+    /// A description of the difference.
+    pub description: String,
+}
+
+/// A summary of the differences between two formats, as computed by
+/// [`crate::format::Format::diff_summary`].
+///
+/// The comparison stops once a set number of differences have been found, so
+/// the counts are lower bounds if the summary is truncated.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DiffSummary {
+    /// The number of differences found in each category.
+    pub counts: BTreeMap<DiffCategory, usize>,
+
+    /// The differences found, in the order they were found.
+    pub examples: Vec<Difference>,
+
+    /// Whether the comparison stopped early because the limit was reached.
+    pub truncated: bool,
+
+    limit: usize,
+}
+
+impl DiffSummary {
+    pub(crate) fn new(limit: usize) -> Self {
+        DiffSummary {
+            counts: BTreeMap::new(),
+            examples: Vec::new(),
+            truncated: false,
+            limit,
+        }
+    }
+
+    /// Check whether the formats were found to be the same.
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty() && !self.truncated
+    }
+
+    /// Record a difference, returning false if the limit has been reached
+    /// and the comparison should stop.
+    pub(crate) fn record<S: Into<String>>(
+        &mut self,
+        category: DiffCategory,
+        description: S,
+    ) -> bool {
+        if self.examples.len() >= self.limit {
+            self.truncated = true;
+            return false;
+        }
+
+        *self.counts.entry(category).or_insert(0) += 1;
+        self.examples.push(Difference {
+            category,
+            description: description.into(),
+        });
+        true
+    }
+}
+
+/// Checksums of the main sections of a format, as computed by
+/// [`crate::format::Format::section_crcs`].
+///
+/// Comparing the checksums of two formats is a quick way to find out which
+/// sections differ, without the detail of a [`DiffSummary`]. Each checksum is
+/// the standard CRC-32 (as used by zlib and PNG) of a canonical serialization
+/// of the section, in which every integer is written in big-endian order at
+/// its natural width:
+///
+/// - `strings`: the UTF-16 code units of the string pool, then the pool offset
+///   of each string as a 32-bit value.
+/// - `eqtb`: the memory words of the table of equivalents, from index zero
+///   through `EQTB_TOP`, in the layout used by the engine.
+/// - `hash`: the memory words of the control-sequence hash table, from
+///   `HASH_OFFSET` through `EQTB_TOP`, with unused slots zeroed.
+/// - `fonts`: the `font_info` words, then the fields of each font in the order
+///   that they're declared in [`crate::fonts::Font`].
+/// - `hyphenation`: the `count` and `next` fields of the exception table, then
+///   the link, word, and list pointer of each exception slot.
+/// - `trie`: the fields of [`crate::hyphenation::HyphenationTrie`] in the
+///   order that they're declared, with each array written element by element.
+///
+/// Because pointers into `mem` and the string pool are included as-is,
+/// sections can have different checksums even when their contents are
+/// equivalent, if unrelated allocations moved things around.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SectionCrcs {
+    /// The checksum of the string table.
+    pub strings: u32,
+
+    /// The checksum of the table of equivalents.
+    pub eqtb: u32,
+
+    /// The checksum of the control-sequence hash table.
+    pub hash: u32,
+
+    /// The checksum of the preloaded fonts.
+    pub fonts: u32,
+
+    /// The checksum of the hyphenation exceptions.
+    pub hyphenation: u32,
+
+    /// The checksum of the hyphenation pattern trie.
+    pub trie: u32,
+}
+
+impl SectionCrcs {
+    /// Get the names of the sections whose checksums differ between these
+    /// and *other*.
+    pub fn changed_sections(&self, other: &SectionCrcs) -> Vec<&'static str> {
+        [
+            ("strings", self.strings, other.strings),
+            ("eqtb", self.eqtb, other.eqtb),
+            ("hash", self.hash, other.hash),
+            ("fonts", self.fonts, other.fonts),
+            ("hyphenation", self.hyphenation, other.hyphenation),
+            ("trie", self.trie, other.trie),
+        ]
+        .iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(name, _, _)| *name)
+        .collect()
+    }
+}
+
+const fn make_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
+
+const CRC_TABLE: [u32; 256] = make_crc_table();
+
+/// An incremental CRC-32 computation.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Crc32(0xFFFF_FFFF)
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.0 = CRC_TABLE[((self.0 ^ *b as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn update_i16(&mut self, value: i16) {
+        self.update(&value.to_be_bytes());
+    }
+
+    pub(crate) fn update_i32(&mut self, value: i32) {
+        self.update(&value.to_be_bytes());
+    }
+
+    pub(crate) fn update_i64(&mut self, value: i64) {
+        self.update(&value.to_be_bytes());
+    }
+
+    pub(crate) fn finish(self) -> u32 {
+        self.0 ^ 0xFFFF_FFFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+        assert_eq!(Crc32::new().finish(), 0);
+    }
+
+    #[test]
+    fn changed_sections() {
+        let a = SectionCrcs::default();
+        let b = SectionCrcs {
+            hash: 1,
+            trie: 2,
+            ..a
+        };
+        assert!(a.changed_sections(&a).is_empty());
+        assert_eq!(a.changed_sections(&b), vec!["hash", "trie"]);
+    }
+
+    #[test]
+    fn limit() {
+        let mut s = DiffSummary::new(2);
+        assert!(s.is_empty());
+        assert!(s.record(DiffCategory::Strings, "a"));
+        assert!(s.record(DiffCategory::Fonts, "b"));
+        assert!(!s.truncated);
+        assert!(!s.record(DiffCategory::Fonts, "c"));
+        assert!(s.truncated);
+        assert_eq!(s.examples.len(), 2);
+        assert_eq!(s.counts[&DiffCategory::Fonts], 1);
+
+        let mut s = DiffSummary::new(0);
+        assert!(!s.record(DiffCategory::Catcodes, "d"));
+        assert!(!s.is_empty());
+    }
+}
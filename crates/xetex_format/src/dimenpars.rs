@@ -14,7 +14,7 @@ use crate::{
 };
 
 /// Information about dimensional parameters.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct DimenPar {
     /// The name of the parameter.
     name: &'static str,
@@ -23,6 +23,24 @@ pub struct DimenPar {
     since: FormatVersion,
 }
 
+impl DimenPar {
+    /// Get the name of this parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the name of the primitive associated with this parameter.
+    pub fn primitive_name(&self) -> String {
+        self.name.replace('_', "")
+    }
+
+    /// Get the name of the symbol giving this parameter's offset from
+    /// `DIMEN_BASE`.
+    pub fn symbol_name(&self) -> String {
+        format!("DIMEN_PAR__{}", self.name.to_lowercase())
+    }
+}
+
 const DIMEN_PARS: &[DimenPar] = &[
     DimenPar {
         name: "par_indent",
@@ -130,11 +148,7 @@ pub fn get_dimenpars_for_version(
     for p in DIMEN_PARS {
         if version >= p.since {
             r.push(*p);
-            symbols.add(
-                SymbolCategory::DimenPars,
-                format!("DIMEN_PAR__{}", p.name.to_lowercase()),
-                n,
-            )?;
+            symbols.add(SymbolCategory::DimenPars, p.symbol_name(), n)?;
             n += 1;
         }
     }
@@ -148,9 +162,9 @@ pub fn emit_c_header_primitives<W: Write>(pars: &[DimenPar], mut stream: W) -> R
     for par in pars {
         writeln!(
             stream,
-            "    {{ \"{}\", ASSIGN_DIMEN, DIMEN_BASE + DIMEN_PAR__{}, xf_prim_init_none }}, \\",
-            par.name.replace('_', ""),
-            par.name.to_lowercase(),
+            "    {{ \"{}\", ASSIGN_DIMEN, DIMEN_BASE + {}, xf_prim_init_none }}, \\",
+            par.primitive_name(),
+            par.symbol_name(),
         )?;
     }
 
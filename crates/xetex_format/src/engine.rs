@@ -5,12 +5,18 @@
 
 //! The overall interface provided by the engine.
 
-use std::io::Write;
+use std::{collections::BTreeMap, io::Write};
 use tectonic_errors::prelude::*;
 
 use crate::{
-    commands::{self, Commands},
-    cshash, dimenpars, enums, eqtb, etexpenalties, gluepars, intpars, locals,
+    catcodes::CatCode,
+    codes,
+    commands::{self, CommandArgument, CommandCode, Commands},
+    cshash, dimenpars, enums, eqtb, etexpenalties,
+    fixed::Fixed,
+    gluepars, intpars, locals,
+    mathcodes::UMathCode,
+    mem::GlueSpec,
     symbols::{SymbolCategory, SymbolTable},
     FormatVersion, LATEST_VERSION,
 };
@@ -42,9 +48,36 @@ pub struct Engine {
 
     /// The e-TeX penalties parameters defined in this engine implementation.
     pub etex_penalties_pars: Vec<etexpenalties::EtexPenaltiesPar>,
+
+    /// The names of the primitives defined in this engine implementation,
+    /// indexed by their command code and argument. Some primitives are
+    /// synonyms, like `\Umathchardef` and `\XeTeXmathchardef`, so a code may
+    /// have several names; they're kept in definition order, and the first
+    /// one is the canonical name.
+    primitives: BTreeMap<(CommandCode, CommandArgument), Vec<String>>,
 }
 
 impl Engine {
+    /// Get an engine definition for a version identified by name.
+    ///
+    /// Format versions don't have release names of their own, so the
+    /// recognized names are `latest`, for [`LATEST_VERSION`], and version
+    /// numbers written like `v32`. Returns None if the name isn't recognized
+    /// or doesn't correspond to a version that this crate supports.
+    pub fn for_named_version(name: &str) -> Option<Engine> {
+        let version = if name == "latest" {
+            LATEST_VERSION
+        } else {
+            name.strip_prefix('v')?.parse().ok()?
+        };
+
+        if version == 0 || version > LATEST_VERSION {
+            return None;
+        }
+
+        Engine::new_for_version(version).ok()
+    }
+
     /// Get an engine definition for a specific version.
     pub fn new_for_version(version: FormatVersion) -> Result<Engine> {
         let mut symbols = SymbolTable::default();
@@ -77,6 +110,64 @@ impl Engine {
 
         let commands = commands::Commands::get_for_version(version, &mut symbols)?;
 
+        let mut primitives: BTreeMap<_, Vec<String>> = BTreeMap::new();
+        let mut add = |code: CommandCode, arg: CommandArgument, name: String| {
+            primitives.entry((code, arg)).or_default().push(name);
+        };
+
+        for (name, code, arg) in commands.primitives(&symbols) {
+            add(code, arg, name.to_owned());
+        }
+
+        let cmd = |s: &str| symbols.lookup(s) as CommandCode;
+        let arg = |base: &str, sym: String| (symbols.lookup(base) + symbols.lookup(&sym)) as i32;
+
+        for par in &int_pars {
+            if let Some(name) = par.primitive_name() {
+                add(cmd("ASSIGN_INT"), arg("INT_BASE", par.symbol_name()), name);
+            }
+        }
+
+        for par in &dimen_pars {
+            add(
+                cmd("ASSIGN_DIMEN"),
+                arg("DIMEN_BASE", par.symbol_name()),
+                par.primitive_name(),
+            );
+        }
+
+        for par in &glue_pars {
+            let code = match par.kind() {
+                gluepars::GlueParKind::Regular => cmd("ASSIGN_GLUE"),
+                gluepars::GlueParKind::Math => cmd("ASSIGN_MU_GLUE"),
+            };
+            add(
+                code,
+                arg("GLUE_BASE", par.symbol_name()),
+                par.primitive_name(),
+            );
+        }
+
+        for par in &local_pars {
+            let code = match par.kind() {
+                locals::LocalParKind::Shape => cmd("SET_SHAPE"),
+                locals::LocalParKind::TokenList => cmd("ASSIGN_TOKS"),
+            };
+            add(
+                code,
+                arg("LOCAL_BASE", par.symbol_name()),
+                par.primitive_name(),
+            );
+        }
+
+        for par in &etex_penalties_pars {
+            add(
+                cmd("SET_SHAPE"),
+                arg("ETEX_PEN_BASE", par.symbol_name()),
+                par.primitive_name(),
+            );
+        }
+
         Ok(Engine {
             version,
             symbols,
@@ -86,6 +177,100 @@ impl Engine {
             glue_pars,
             local_pars,
             etex_penalties_pars,
+            primitives,
+        })
+    }
+
+    /// Get the category code that character *c* has in a freshly initialized
+    /// engine, before any format setup.
+    pub fn default_catcode(&self, c: i32) -> CatCode {
+        CatCode::initial_for(c)
+    }
+
+    /// Get the `\lccode` that character *c* has in a freshly initialized
+    /// engine.
+    pub fn initial_lc_code(&self, c: i32) -> i32 {
+        codes::initial_lc_code(c)
+    }
+
+    /// Get the `\uccode` that character *c* has in a freshly initialized
+    /// engine.
+    pub fn initial_uc_code(&self, c: i32) -> i32 {
+        codes::initial_uc_code(c)
+    }
+
+    /// Get the `\sfcode` that character *c* has in a freshly initialized
+    /// engine.
+    pub fn initial_sf_code(&self, c: i32) -> i32 {
+        codes::initial_sf_code(c)
+    }
+
+    /// Get the `\delcode` that character *c* has in a freshly initialized
+    /// engine.
+    pub fn initial_del_code(&self, c: i32) -> i32 {
+        codes::initial_del_code(c)
+    }
+
+    /// Get the `\Umathcode` that character *c* has in a freshly initialized
+    /// engine.
+    pub fn initial_math_code(&self, c: i32) -> UMathCode {
+        UMathCode::default_for(c)
+    }
+
+    /// Get the value that the integer parameter *name* has in a freshly
+    /// initialized engine, or None if this engine has no such parameter.
+    ///
+    /// Parameters are named as in the engine's tables, e.g. `end_line_char`.
+    pub fn initial_int_parameter(&self, name: &str) -> Option<i32> {
+        self.int_pars
+            .iter()
+            .find(|par| par.name() == name)
+            .map(|par| par.initial_value())
+    }
+
+    /// Get the value that the dimension parameter *name* has in a freshly
+    /// initialized engine, or None if this engine has no such parameter.
+    ///
+    /// All dimension parameters start out as zero.
+    pub fn initial_dimen_parameter(&self, name: &str) -> Option<Fixed> {
+        self.dimen_pars
+            .iter()
+            .find(|par| par.name() == name)
+            .map(|_| Fixed(0))
+    }
+
+    /// Get the value that the glue parameter *name* has in a freshly
+    /// initialized engine, or None if this engine has no such parameter.
+    ///
+    /// All glue parameters start out as `zero_glue`.
+    pub fn initial_glue_parameter(&self, name: &str) -> Option<GlueSpec> {
+        self.glue_pars
+            .iter()
+            .find(|par| par.name() == name)
+            .map(|_| GlueSpec::default())
+    }
+
+    /// Get the name of the primitive with the given command code and
+    /// argument, if there is one.
+    ///
+    /// If several primitives share the code, this is the canonical one: the
+    /// first that the engine defines. The name is returned without a leading
+    /// backslash.
+    pub fn primitive_name(&self, cmd: CommandCode, chr: CommandArgument) -> Option<&str> {
+        self.primitives
+            .get(&(cmd, chr))
+            .and_then(|names| names.first())
+            .map(|s| s.as_ref())
+    }
+
+    /// Iterate over the primitives defined in this engine implementation,
+    /// yielding their command codes, arguments, and names.
+    ///
+    /// Every name is yielded, including synonyms that share a code with
+    /// another primitive.
+    pub fn primitives(&self) -> impl Iterator<Item = (CommandCode, CommandArgument, &str)> {
+        self.primitives.iter().flat_map(|((cmd, chr), names)| {
+            names.iter().map(move |name| (*cmd, *chr, name.as_ref()))
         })
     }
 
@@ -357,6 +542,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn primitive_names() {
+        let eng = Engine::default();
+        let s = |sym| eng.symbols.lookup(sym);
+        let name = |cmd, chr| eng.primitive_name(cmd as CommandCode, chr as CommandArgument);
+
+        assert_eq!(
+            name(s("ASSIGN_INT"), s("INT_BASE") + s("INT_PAR__tolerance")),
+            Some("tolerance")
+        );
+        assert_eq!(
+            name(s("ASSIGN_TOKS"), s("LOCAL_BASE") + s("LOCAL__every_par")),
+            Some("everypar")
+        );
+        assert_eq!(name(s("REGISTER"), 0), Some("count"));
+        assert_eq!(name(s("ASSIGN_INT"), s("COUNT_BASE")), None);
+
+        // Synonyms share a code, but each name is still listed.
+        let code = |n| {
+            eng.primitives()
+                .find(|(_, _, name)| *name == n)
+                .map(|(cmd, chr, _)| (cmd, chr))
+        };
+        let (cmd, chr) = code("Umathchardef").unwrap();
+        assert_eq!(code("XeTeXmathchardef"), Some((cmd, chr)));
+        assert_eq!(eng.primitive_name(cmd, chr), Some("Umathchardef"));
+    }
+
     /// Check the various "bad" conditions tested in the classical
     /// implementation. Most of them have to do with parameters like
     /// `min_halfword` that we don't change, so there are only a few to check.
@@ -408,4 +621,35 @@ mod tests {
         // 31: format_default_length <= file_name_size
         // 41: 2 * max_halfword >= mem_top - mem_min
     }
+
+    #[test]
+    fn initial_values() {
+        let eng = Engine::default();
+
+        assert_eq!(eng.default_catcode(92), CatCode::Escape);
+        assert_eq!(eng.initial_lc_code('Q' as i32), 'q' as i32);
+        assert_eq!(eng.initial_math_code('7' as i32).family, 0);
+        assert_eq!(eng.initial_int_parameter("escape_char"), Some(92));
+        assert_eq!(eng.initial_int_parameter("pretolerance"), Some(0));
+        assert_eq!(eng.initial_int_parameter("bogus"), None);
+        assert_eq!(eng.initial_dimen_parameter("hsize"), Some(Fixed(0)));
+        assert_eq!(
+            eng.initial_glue_parameter("baseline_skip"),
+            Some(GlueSpec::default())
+        );
+        assert_eq!(eng.initial_glue_parameter("hsize"), None);
+    }
+
+    #[test]
+    fn named_versions() {
+        let eng = Engine::for_named_version("latest").unwrap();
+        assert_eq!(eng.version, LATEST_VERSION);
+
+        let eng = Engine::for_named_version("v31").unwrap();
+        assert_eq!(eng.version, 31);
+
+        assert!(Engine::for_named_version("31").is_none());
+        assert!(Engine::for_named_version("v0").is_none());
+        assert!(Engine::for_named_version("bogus").is_none());
+    }
 }
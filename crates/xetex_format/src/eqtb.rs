@@ -70,14 +70,15 @@
 //! `eqtb_top` is `eqtb_size + hash_extra` and the total addressed size of the
 //! array is `eqtb_top + 1`.
 
-use nom::{multi::count, number::complete::be_u8, IResult};
+use byteorder::ByteOrder;
+use nom::bytes::complete::take;
 use tectonic_errors::prelude::*;
 
 use crate::{
     base::{self, SIZEOF_MEMORY_WORD, TEX_NULL},
     commands::CommandCode,
     engine::Engine,
-    parseutils,
+    parseutils::{self, ParseResult},
     symbols::{SymbolCategory, SymbolTable},
 };
 
@@ -88,26 +89,101 @@ pub struct EquivalenciesTable {
 
 pub type EqtbPointer = i32;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub struct EqtbEntry {
     pub level: i16,
     pub ty: CommandCode,
     pub value: i32,
 }
 
+/// A high-level classification of an eqtb entry.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum EntryKind {
+    /// The entry has the meaning of a primitive.
+    Primitive,
+
+    /// The entry is a macro, possibly `\long` and/or `\outer`.
+    Macro,
+
+    /// The entry was created with `\chardef`.
+    CharDef,
+
+    /// The entry was created with `\mathchardef` or `\Umathchardef`.
+    MathCharDef,
+
+    /// The entry selects a font, e.g. one created with `\font`.
+    FontSelector,
+
+    /// The entry refers to a register, e.g. one created with `\countdef`.
+    Register,
+
+    /// The entry is an implicit character, created with something like
+    /// `\let\bgroup={`.
+    LetAlias,
+
+    /// The entry is undefined.
+    Undefined,
+
+    /// Something else, with the given command code.
+    Other(CommandCode),
+}
+
+impl EqtbEntry {
+    /// Classify this entry according to its command code and argument.
+    pub fn classify(&self, engine: &Engine) -> EntryKind {
+        let cmd = |s: &str| engine.symbols.lookup(s) as CommandCode;
+
+        if self.ty == cmd("UNDEFINED_CS") {
+            EntryKind::Undefined
+        } else if self.ty >= cmd("CALL") && self.ty <= cmd("LONG_OUTER_CALL") {
+            EntryKind::Macro
+        } else if self.ty == cmd("CHAR_GIVEN") {
+            EntryKind::CharDef
+        } else if self.ty == cmd("MATH_GIVEN") || self.ty == cmd("XETEX_MATH_GIVEN") {
+            EntryKind::MathCharDef
+        } else if self.ty == cmd("SET_FONT") {
+            EntryKind::FontSelector
+        } else if engine.primitive_name(self.ty, self.value).is_some() {
+            EntryKind::Primitive
+        } else if self.ty == cmd("REGISTER")
+            || self.ty == cmd("TOKS_REGISTER")
+            || self.ty == cmd("ASSIGN_INT")
+            || self.ty == cmd("ASSIGN_DIMEN")
+            || self.ty == cmd("ASSIGN_GLUE")
+            || self.ty == cmd("ASSIGN_MU_GLUE")
+            || self.ty == cmd("ASSIGN_TOKS")
+        {
+            // The primitives with these command codes were handled above, so
+            // this must be something like `\countdef`.
+            EntryKind::Register
+        } else if self.ty <= cmd("MAX_CHAR_CODE") {
+            EntryKind::LetAlias
+        } else {
+            EntryKind::Other(self.ty)
+        }
+    }
+}
+
 impl EquivalenciesTable {
+    /// Get the raw memory words of the table.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.eqtb[..]
+    }
+
+    /// Parse the eqtb, storing it in *buf*.
     pub(crate) fn parse<'a>(
         input: &'a [u8],
         engine: &Engine,
         hash_high: i32,
-    ) -> IResult<&'a [u8], Self> {
+        buf: Vec<u8>,
+    ) -> ParseResult<'a, Self> {
         let eqtb_size = engine.symbols.lookup("EQTB_SIZE") as usize;
         let eqtb_top = engine.symbols.lookup("EQTB_TOP") as usize;
         let undefined_control_sequence =
             engine.symbols.lookup("UNDEFINED_CONTROL_SEQUENCE") as EqtbPointer;
         let undefined_cs_cmd = engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
 
-        let mut eqtb = vec![0; (eqtb_top as usize + 1) * SIZEOF_MEMORY_WORD];
+        let mut eqtb = parseutils::zeroed_buffer(buf, (eqtb_top + 1) * SIZEOF_MEMORY_WORD);
 
         write_eqtb_type(&mut eqtb[..], undefined_control_sequence, undefined_cs_cmd);
         write_eqtb_value(&mut eqtb[..], undefined_control_sequence, TEX_NULL);
@@ -136,8 +212,8 @@ impl EquivalenciesTable {
 
             // TODO: read straight into eqtb?
             let nb = n as usize * SIZEOF_MEMORY_WORD;
-            let (ii, block) = count(be_u8, nb)(ii)?;
-            eqtb[k * SIZEOF_MEMORY_WORD..k * SIZEOF_MEMORY_WORD + nb].copy_from_slice(&block[..]);
+            let (ii, block) = take(nb)(ii)?;
+            eqtb[k * SIZEOF_MEMORY_WORD..k * SIZEOF_MEMORY_WORD + nb].copy_from_slice(block);
             k += n as usize;
 
             let (ii, m) = parseutils::ranged_be_i32(0, (eqtb_size + 1 - k) as i32)(ii)?;
@@ -160,21 +236,80 @@ impl EquivalenciesTable {
         if hash_high > 0 {
             // TODO: read straight into eqtb?
             let nb = hash_high as usize * SIZEOF_MEMORY_WORD;
-            let (new_input, block) = count(be_u8, nb)(input)?;
+            let (new_input, block) = take(nb)(input)?;
             input = new_input;
             let ofs = (eqtb_size + 1) * SIZEOF_MEMORY_WORD;
-            eqtb[ofs..ofs + nb].copy_from_slice(&block[..]);
+            eqtb[ofs..ofs + nb].copy_from_slice(block);
         }
 
         Ok((input, EquivalenciesTable { eqtb }))
     }
 
+    /// Skip over the table of equivalents without decoding it.
+    pub(crate) fn skip<'a>(
+        input: &'a [u8],
+        engine: &Engine,
+        hash_high: i32,
+    ) -> ParseResult<'a, ()> {
+        let eqtb_size = engine.symbols.lookup("EQTB_SIZE") as usize;
+
+        let mut k = 1;
+        let mut input = input;
+
+        loop {
+            let (ii, n) = parseutils::ranged_be_i32(1, (eqtb_size + 1 - k) as i32)(input)?;
+            let (ii, _) = take(n as usize * SIZEOF_MEMORY_WORD)(ii)?;
+            k += n as usize;
+
+            let (ii, m) = parseutils::ranged_be_i32(0, (eqtb_size + 1 - k) as i32)(ii)?;
+            input = ii;
+            k += m as usize;
+
+            if k > eqtb_size {
+                break;
+            }
+        }
+
+        if hash_high > 0 {
+            let (ii, _) = take(hash_high as usize * SIZEOF_MEMORY_WORD)(input)?;
+            input = ii;
+        }
+
+        Ok((input, ()))
+    }
+
+    /// Give up the table's storage so that it can be reused.
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        self.eqtb
+    }
+
     pub fn decode(&self, index: EqtbPointer) -> EqtbEntry {
         let level = base::memword_read_b16_s0(&self.eqtb[..], index);
         let ty = base::memword_read_b16_s1(&self.eqtb[..], index);
         let value = base::memword_read_b32_s1(&self.eqtb[..], index);
         EqtbEntry { level, ty, value }
     }
+
+    /// Get the undecoded memory word at *index*, as it was stored in the
+    /// format file.
+    ///
+    /// This is a debugging aid, for comparing the results of [`Self::decode`]
+    /// to dumps of the engine's own memory. An error is returned if *index* is
+    /// out of bounds.
+    pub fn raw(&self, index: EqtbPointer) -> Result<i64> {
+        let n_words = self.eqtb.len() / SIZEOF_MEMORY_WORD;
+        ensure!(
+            index >= 0 && (index as usize) < n_words,
+            "eqtb index {} is out of bounds (table size {})",
+            index,
+            n_words
+        );
+
+        let i = index as usize * SIZEOF_MEMORY_WORD;
+        Ok(base::FormatEndian::read_i64(
+            &self.eqtb[i..i + SIZEOF_MEMORY_WORD],
+        ))
+    }
 }
 
 /// Equivalent of TeX `eq_level`
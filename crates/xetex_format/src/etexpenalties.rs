@@ -18,7 +18,7 @@ use crate::{
 };
 
 /// Information about e-TeX penalties parameters.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct EtexPenaltiesPar {
     /// The name of the parameter.
     name: &'static str,
@@ -27,6 +27,24 @@ pub struct EtexPenaltiesPar {
     since: FormatVersion,
 }
 
+impl EtexPenaltiesPar {
+    /// Get the name of this parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the name of the primitive associated with this parameter.
+    pub fn primitive_name(&self) -> String {
+        self.name.replace('_', "")
+    }
+
+    /// Get the name of the symbol giving this parameter's offset from
+    /// `ETEX_PEN_BASE`.
+    pub fn symbol_name(&self) -> String {
+        format!("ETEX_PENALTIES_PAR__{}", self.name.to_lowercase())
+    }
+}
+
 const ETEX_PENALTIES_PARS: &[EtexPenaltiesPar] = &[
     EtexPenaltiesPar {
         name: "inter_line_penalties",
@@ -58,11 +76,7 @@ pub fn get_etex_penalties_pars_for_version(
     for p in ETEX_PENALTIES_PARS {
         if version >= p.since {
             r.push(*p);
-            symbols.add(
-                SymbolCategory::EtexPenaltiesPars,
-                format!("ETEX_PENALTIES_PAR__{}", p.name.to_lowercase()),
-                n,
-            )?;
+            symbols.add(SymbolCategory::EtexPenaltiesPars, p.symbol_name(), n)?;
             n += 1;
         }
     }
@@ -76,9 +90,9 @@ pub fn emit_c_header_primitives<W: Write>(pars: &[EtexPenaltiesPar], mut stream:
     for par in pars {
         writeln!(
             stream,
-            "    {{ \"{}\", SET_SHAPE, ETEX_PEN_BASE + ETEX_PENALTIES_PAR__{}, xf_prim_init_none }}, \\",
-            par.name.replace('_', ""),
-            par.name.to_lowercase(),
+            "    {{ \"{}\", SET_SHAPE, ETEX_PEN_BASE + {}, xf_prim_init_none }}, \\",
+            par.primitive_name(),
+            par.symbol_name(),
         )?;
     }
 
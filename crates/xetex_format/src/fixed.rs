@@ -0,0 +1,225 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! TeX's fixed-point dimensions.
+//!
+//! TeX represents dimensions as "scaled" integers, counting units of `1/65536`
+//! of a printer's point ("scaled points", or "sp").
+
+use std::fmt;
+
+/// The number of scaled points in a point.
+pub const UNITY: i32 = 0x1_0000;
+
+/// A dimension, in TeX scaled points.
+///
+/// Dimension-valued accessors return this type rather than a bare `i32`, so
+/// that the conversion from scaled points can't be forgotten.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    /// Get the value of this dimension in scaled points.
+    pub fn as_sp(self) -> i32 {
+        self.0
+    }
+
+    /// Convert this dimension to printer's points.
+    pub fn as_pt(self) -> f64 {
+        self.0 as f64 / UNITY as f64
+    }
+
+    /// Convert this dimension to inches.
+    ///
+    /// There are exactly 72.27 printer's points in an inch.
+    pub fn as_in(self) -> f64 {
+        self.as_pt() / 72.27
+    }
+
+    /// Convert this dimension to centimeters.
+    pub fn as_cm(self) -> f64 {
+        self.as_in() * 2.54
+    }
+
+    /// Convert this dimension to millimeters.
+    pub fn as_mm(self) -> f64 {
+        self.as_in() * 25.4
+    }
+
+    /// Convert this dimension to an arbitrary unit.
+    ///
+    /// This is the general form of the `as_*` conversions above, e.g.
+    /// `x.to_unit(TexUnit::In)` is `x.as_in()`.
+    pub fn to_unit(&self, unit: TexUnit) -> f64 {
+        let (num, denom) = unit.ratio();
+        (self.0 as i64 * denom) as f64 / num as f64
+    }
+}
+
+/// The physical units that TeX understands, as in `\hsize=6.5in`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TexUnit {
+    /// Printer's points.
+    Pt,
+
+    /// Big (PostScript) points, 72 to the inch.
+    Bp,
+
+    /// Inches.
+    In,
+
+    /// Centimeters.
+    Cm,
+
+    /// Millimeters.
+    Mm,
+
+    /// Picas, 12 points each.
+    Pc,
+
+    /// Didot points.
+    Dd,
+
+    /// Ciceros, 12 Didot points each.
+    Cc,
+
+    /// Scaled points, the units in which TeX stores dimensions.
+    Sp,
+}
+
+impl TexUnit {
+    /// Get the abbreviation that TeX uses for this unit, e.g. `pt`.
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            TexUnit::Pt => "pt",
+            TexUnit::Bp => "bp",
+            TexUnit::In => "in",
+            TexUnit::Cm => "cm",
+            TexUnit::Mm => "mm",
+            TexUnit::Pc => "pc",
+            TexUnit::Dd => "dd",
+            TexUnit::Cc => "cc",
+            TexUnit::Sp => "sp",
+        }
+    }
+
+    /// Look up a unit by its TeX abbreviation.
+    pub fn from_abbrev(abbrev: &str) -> Option<TexUnit> {
+        Some(match abbrev {
+            "pt" => TexUnit::Pt,
+            "bp" => TexUnit::Bp,
+            "in" => TexUnit::In,
+            "cm" => TexUnit::Cm,
+            "mm" => TexUnit::Mm,
+            "pc" => TexUnit::Pc,
+            "dd" => TexUnit::Dd,
+            "cc" => TexUnit::Cc,
+            "sp" => TexUnit::Sp,
+            _ => return None,
+        })
+    }
+
+    /// Get the size of this unit in scaled points, as a fraction.
+    ///
+    /// These are the exact ratios that TeX uses when scanning dimensions
+    /// (*TeX: The Program*, §458), scaled up by [`UNITY`].
+    fn ratio(self) -> (i64, i64) {
+        let (num, denom) = match self {
+            TexUnit::Pt => (1, 1),
+            TexUnit::Bp => (7227, 7200),
+            TexUnit::In => (7227, 100),
+            TexUnit::Cm => (7227, 254),
+            TexUnit::Mm => (7227, 2540),
+            TexUnit::Pc => (12, 1),
+            TexUnit::Dd => (1238, 1157),
+            TexUnit::Cc => (14856, 1157),
+            TexUnit::Sp => return (1, 1),
+        };
+
+        (num * UNITY as i64, denom)
+    }
+}
+
+/// An alias for [`Fixed`], using TeX's own name for these values.
+pub type Scaled = Fixed;
+
+impl From<i32> for Fixed {
+    fn from(sp: i32) -> Self {
+        Fixed(sp)
+    }
+}
+
+/// Formats the dimension just as TeX's `\the` would, e.g. `12.0pt`.
+///
+/// This uses the algorithm of TeX's `print_scaled`, which prints the fewest
+/// decimal digits needed to reproduce the exact value when read back in.
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Widen to avoid overflow when negating `i32::MIN`.
+        let mut s = self.0 as i64;
+        let unity = UNITY as i64;
+
+        if s < 0 {
+            write!(f, "-")?;
+            s = -s;
+        }
+
+        write!(f, "{}.", s / unity)?;
+        s = 10 * (s % unity) + 5;
+        let mut delta = 10;
+
+        loop {
+            if delta > unity {
+                s += 0x8000 - 50000; // round the last digit
+            }
+
+            write!(f, "{}", s / unity)?;
+            s = 10 * (s % unity);
+            delta *= 10;
+
+            if s <= delta {
+                break;
+            }
+        }
+
+        write!(f, "pt")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_scaled() {
+        assert_eq!(Fixed(0).to_string(), "0.0pt");
+        assert_eq!(Fixed(UNITY).to_string(), "1.0pt");
+        assert_eq!(Fixed(-UNITY / 2).to_string(), "-0.5pt");
+        assert_eq!(Fixed(1).to_string(), "0.00002pt");
+        // \hsize in plain TeX: 6.5in
+        assert_eq!(Fixed(30_785_863).to_string(), "469.75499pt");
+    }
+
+    #[test]
+    fn conversions() {
+        let inch = Fixed(4_736_286); // 72.27pt
+        assert!((inch.as_in() - 1.0).abs() < 1e-6);
+        assert!((inch.as_mm() - 25.4).abs() < 1e-4);
+        assert!((inch.as_cm() - 2.54).abs() < 1e-5);
+
+        assert_eq!(Fixed(UNITY * 12).to_unit(TexUnit::Pc), 1.0);
+        assert_eq!(Fixed(12_345).to_unit(TexUnit::Sp), 12_345.0);
+        assert!((inch.to_unit(TexUnit::Bp) - 72.0).abs() < 1e-4);
+        assert!((inch.to_unit(TexUnit::In) - inch.as_in()).abs() < 1e-9);
+        assert!((Fixed(UNITY * 1238).to_unit(TexUnit::Dd) - 1157.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unit_abbrevs() {
+        for abbrev in ["pt", "bp", "in", "cm", "mm", "pc", "dd", "cc", "sp"].iter() {
+            assert_eq!(TexUnit::from_abbrev(abbrev).unwrap().abbrev(), *abbrev);
+        }
+
+        assert_eq!(TexUnit::from_abbrev("em"), None);
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! The fonts preloaded into a format.
+//!
+//! TeX stores font metric data in a big array of memory words, `font_info`,
+//! along with a collection of parallel arrays giving per-font information,
+//! indexed by font number. Font number zero (`FONT_BASE`) is always the
+//! `\nullfont`.
+//!
+//! Only TFM-based fonts can appear here. XeTeX refuses to `\dump` a format
+//! while any native (OpenType/AAT/Graphite) fonts or font mappings are
+//! loaded, so formats never contain native-font records, and information like
+//! glyph counts or enabled OpenType features can't be recovered from them.
+
+use nom::{
+    multi::count,
+    number::complete::{be_i16, be_i32, be_i64},
+};
+
+use crate::{
+    base::{MAX_HALFWORD, MIN_HALFWORD},
+    engine::Engine,
+    fixed::Fixed,
+    mem::MemPointer,
+    parseutils::{self, ParseResult},
+    stringtable::{StrPointer, TOO_BIG_CHAR},
+};
+
+/// Information about a single preloaded font.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Font {
+    /// The TFM checksum of the font, stored as four 16-bit quarterwords.
+    pub check: i64,
+
+    /// The "at" size of the font.
+    pub size: Fixed,
+
+    /// The design size of the font.
+    pub design_size: Fixed,
+
+    /// The number of font parameters (`\fontdimen`s).
+    pub params: i32,
+
+    /// The `\hyphenchar` of the font.
+    pub hyphen_char: i32,
+
+    /// The `\skewchar` of the font.
+    pub skew_char: i32,
+
+    /// The string pointer of the font's name.
+    pub name: StrPointer,
+
+    /// The string pointer of the font's file "area" (directory).
+    pub area: StrPointer,
+
+    /// The smallest character code in the font.
+    pub bc: i16,
+
+    /// The largest character code in the font.
+    pub ec: i16,
+
+    /// Base offset of the character info words in `font_info`.
+    pub char_base: i32,
+
+    /// Base offset of the character widths in `font_info`.
+    pub width_base: i32,
+
+    /// Base offset of the character heights in `font_info`.
+    pub height_base: i32,
+
+    /// Base offset of the character depths in `font_info`.
+    pub depth_base: i32,
+
+    /// Base offset of the character italic corrections in `font_info`.
+    pub italic_base: i32,
+
+    /// Base offset of the ligature/kerning program in `font_info`.
+    pub lig_kern_base: i32,
+
+    /// Base offset of the kerns in `font_info`.
+    pub kern_base: i32,
+
+    /// Base offset of the extensible character recipes in `font_info`.
+    pub exten_base: i32,
+
+    /// Base offset of the font parameters in `font_info`.
+    pub param_base: i32,
+
+    /// Pointer to the font's interword glue specification in `mem`, or null.
+    pub glue: MemPointer,
+
+    /// Start of the font's boundary-character ligature program.
+    pub bchar_label: i32,
+
+    /// The font's right boundary character, or `TOO_BIG_CHAR` if none.
+    pub bchar: i32,
+
+    /// The font's right boundary character, if it doesn't exist in the font.
+    pub false_bchar: i32,
+}
+
+/// The font information saved in a format.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FontTable {
+    /// The `font_info` array of font metric data.
+    pub info: Vec<i64>,
+
+    /// The fonts, indexed by font number.
+    pub fonts: Vec<Font>,
+}
+
+impl FontTable {
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        engine: &Engine,
+        lo_mem_max: MemPointer,
+    ) -> ParseResult<'a, Self> {
+        let max_fonts = engine.symbols.lookup("MAX_FONT_MAX") as i32;
+
+        let (input, fmem_ptr) = parseutils::ranged_be_i32(7, 147483647)(input)?;
+        let (input, info) = parseutils::sized_count(be_i64, fmem_ptr as usize, 8)(input)?;
+
+        // NB: FONT_BASE = 0
+        let (input, font_ptr) = parseutils::ranged_be_i32(0, max_fonts)(input)?;
+
+        let n_fonts = font_ptr as usize + 1;
+        let (input, check) = count(be_i64, n_fonts)(input)?;
+        let (input, size) = count(be_i32, n_fonts)(input)?;
+        let (input, design_size) = count(be_i32, n_fonts)(input)?;
+        let (input, params) = count(
+            parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD),
+            n_fonts,
+        )(input)?;
+        let (input, hyphen_char) = count(be_i32, n_fonts)(input)?;
+        let (input, skew_char) = count(be_i32, n_fonts)(input)?;
+        let (input, name) = count(be_i32, n_fonts)(input)?;
+        let (input, area) = count(be_i32, n_fonts)(input)?;
+        let (input, bc) = count(be_i16, n_fonts)(input)?;
+        let (input, ec) = count(be_i16, n_fonts)(input)?;
+        let (input, char_base) = count(be_i32, n_fonts)(input)?;
+        let (input, width_base) = count(be_i32, n_fonts)(input)?;
+        let (input, height_base) = count(be_i32, n_fonts)(input)?;
+        let (input, depth_base) = count(be_i32, n_fonts)(input)?;
+        let (input, italic_base) = count(be_i32, n_fonts)(input)?;
+        let (input, lig_kern_base) = count(be_i32, n_fonts)(input)?;
+        let (input, kern_base) = count(be_i32, n_fonts)(input)?;
+        let (input, exten_base) = count(be_i32, n_fonts)(input)?;
+        let (input, param_base) = count(be_i32, n_fonts)(input)?;
+        let (input, glue) =
+            count(parseutils::ranged_be_i32(MIN_HALFWORD, lo_mem_max), n_fonts)(input)?;
+        let (input, bchar_label) =
+            count(parseutils::ranged_be_i32(0, fmem_ptr - 1), n_fonts)(input)?;
+        let (input, bchar) = count(parseutils::ranged_be_i32(0, TOO_BIG_CHAR), n_fonts)(input)?;
+        let (input, false_bchar) =
+            count(parseutils::ranged_be_i32(0, TOO_BIG_CHAR), n_fonts)(input)?;
+
+        let fonts = (0..n_fonts)
+            .map(|i| Font {
+                check: check[i],
+                size: Fixed(size[i]),
+                design_size: Fixed(design_size[i]),
+                params: params[i],
+                hyphen_char: hyphen_char[i],
+                skew_char: skew_char[i],
+                name: name[i],
+                area: area[i],
+                bc: bc[i],
+                ec: ec[i],
+                char_base: char_base[i],
+                width_base: width_base[i],
+                height_base: height_base[i],
+                depth_base: depth_base[i],
+                italic_base: italic_base[i],
+                lig_kern_base: lig_kern_base[i],
+                kern_base: kern_base[i],
+                exten_base: exten_base[i],
+                param_base: param_base[i],
+                glue: glue[i],
+                bchar_label: bchar_label[i],
+                bchar: bchar[i],
+                false_bchar: false_bchar[i],
+            })
+            .collect();
+
+        Ok((input, FontTable { info, fonts }))
+    }
+}
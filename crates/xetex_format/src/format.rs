@@ -4,29 +4,49 @@
 //! Decode a format file.
 
 use nom::{
-    multi::count,
-    number::complete::{be_i16, be_i32, be_i64, be_u16},
-    Err as NomErr, IResult,
+    bytes::complete::take, multi::count, number::complete::be_i32, sequence::pair, Err as NomErr,
 };
-use std::io::Write;
-use tectonic_errors::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::TryFrom,
+    fs,
+    io::Write,
+    ops::{BitOr, BitOrAssign, Range},
+    path::{Path, PathBuf},
+};
+use tectonic_errors::{anyhow::Context, prelude::*};
 
 use crate::{
-    base::{MAX_HALFWORD, MIN_HALFWORD, TEX_NULL},
-    catcodes::CatCode,
-    commands::CommandCode,
+    base::{self, MAX_REG_NUM, NUMBER_REGS, TEX_NULL},
+    catcodes::{self, CatCode},
+    codes,
+    commands::{CommandArgument, CommandCode},
     cshash,
+    diff::{Crc32, DiffCategory, DiffSummary, SectionCrcs},
+    dimenpars,
     engine::Engine,
+    enums::simple::MathFontSizes,
     eqtb::{self, EqtbPointer},
-    mem, parseutils, stringtable,
+    fixed::Fixed,
+    fonts, hyphenation, input, intpars,
+    locals::LocalParKind,
+    mathcodes::UMathCode,
+    mem,
+    parseutils::{self, ParseResult},
+    settings, snapshot,
+    stringtable::{self, TOO_BIG_CHAR},
     tokenlist::Token,
-    FormatVersion,
+    FormatVersion, LATEST_VERSION,
 };
 
 /// Saved Tectonic/XeTeX engine state, decoded into memory.
 ///
 /// This public API of this structure isn't yet complete. It parses format files
 /// but does not yet provide proper runtime introspection of the results.
+///
+/// A `Format` is immutable once parsed: all of its accessors take `&self`, and
+/// it's `Send` and `Sync`. To share a large format between threads without
+/// copying it, wrap it in an [`std::sync::Arc`].
 #[derive(Debug)]
 #[allow(dead_code)] // TEMPORARY!
 pub struct Format {
@@ -35,176 +55,2594 @@ pub struct Format {
     mem: mem::Memory,
     eqtb: eqtb::EquivalenciesTable,
     cshash: cshash::ControlSeqHash,
+    primitives: BTreeMap<(CommandCode, CommandArgument), Vec<String>>,
+    fonts: fonts::FontTable,
+    hyph_exceptions: hyphenation::HyphenationExceptions,
+    trie: hyphenation::HyphenationTrie,
+    trailing_bytes: usize,
+    footer_ok: bool,
+}
+
+/// A selection of sections to include in a [`Format::dump_report`].
+///
+/// Selections can be combined with the `|` operator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DumpSections(u32);
+
+impl DumpSections {
+    /// No sections at all.
+    pub const NONE: DumpSections = DumpSections(0);
+
+    /// The string table.
+    pub const STRINGS: DumpSections = DumpSections(1 << 0);
+
+    /// The category codes.
+    pub const CATCODES: DumpSections = DumpSections(1 << 1);
+
+    /// The definitions of the active characters.
+    pub const ACTIVES: DumpSections = DumpSections(1 << 2);
+
+    /// The preloaded fonts.
+    pub const FONTS: DumpSections = DumpSections(1 << 3);
+
+    /// The registers with non-default values.
+    pub const REGISTERS: DumpSections = DumpSections(1 << 4);
+
+    /// The hyphenation exceptions and patterns.
+    pub const HYPHENATION: DumpSections = DumpSections(1 << 5);
+
+    /// The primitives.
+    pub const PRIMITIVES: DumpSections = DumpSections(1 << 6);
+
+    /// The job startup tokens, `\everyjob`.
+    pub const EVERY_JOB: DumpSections = DumpSections(1 << 7);
+
+    /// All of the sections.
+    pub const ALL: DumpSections = DumpSections((1 << 8) - 1);
+
+    /// Check whether this selection includes all of the sections in *other*.
+    pub fn contains(self, other: DumpSections) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for DumpSections {
+    type Output = DumpSections;
+
+    fn bitor(self, rhs: DumpSections) -> DumpSections {
+        DumpSections(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for DumpSections {
+    fn bitor_assign(&mut self, rhs: DumpSections) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Options for [`Format::dump_catcodes_with`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CatcodeDumpOptions {
+    /// Whether to list characters whose category codes are the same as in a
+    /// freshly initialized engine. If false, these characters are summarized
+    /// in a single line at the end of the dump, so that the characters that
+    /// the format has changed stand out.
+    pub show_defaults: bool,
+}
+
+impl Default for CatcodeDumpOptions {
+    fn default() -> Self {
+        CatcodeDumpOptions {
+            show_defaults: true,
+        }
+    }
 }
 
 // Parsing
 
 const HEADER_MAGIC: i32 = 0x54_54_4E_43; // ASCII "TTNC"
 const FOOTER_MAGIC: i32 = 0x00_00_02_9A;
-const TOO_BIG_CHAR: i32 = 0x0001_0000;
 
-const HYPH_SIZE: usize = 8191;
+/// The size of the header and engine parameters that start every format file.
+const MIN_HEADER_SIZE: usize = 7 * 4;
 
-const TRIE_OP_SIZE: i32 = 35111;
-
-const BIGGEST_LANG: usize = 255;
+/// The magic bytes that start gzip-compressed data.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 const MAX_USV: i32 = crate::base::NUMBER_USVS as i32;
 
+/// Look-alike characters that [`confusable_skeleton`] folds together, each
+/// paired with the ASCII character that it resembles.
+const CONFUSABLES: &[(char, char)] = &[
+    // Greek letters that look like Latin ones
+    ('\u{391}', 'A'),
+    ('\u{392}', 'B'),
+    ('\u{395}', 'E'),
+    ('\u{396}', 'Z'),
+    ('\u{397}', 'H'),
+    ('\u{399}', 'I'),
+    ('\u{39A}', 'K'),
+    ('\u{39C}', 'M'),
+    ('\u{39D}', 'N'),
+    ('\u{39F}', 'O'),
+    ('\u{3A1}', 'P'),
+    ('\u{3A4}', 'T'),
+    ('\u{3A5}', 'Y'),
+    ('\u{3A7}', 'X'),
+    ('\u{3B1}', 'a'),
+    ('\u{3B9}', 'i'),
+    ('\u{3BA}', 'k'),
+    ('\u{3BD}', 'v'),
+    ('\u{3BF}', 'o'),
+    ('\u{3C1}', 'p'),
+    ('\u{3C5}', 'u'),
+    ('\u{3C7}', 'x'),
+    // Cyrillic letters that look like Latin ones
+    ('\u{405}', 'S'),
+    ('\u{406}', 'I'),
+    ('\u{408}', 'J'),
+    ('\u{410}', 'A'),
+    ('\u{412}', 'B'),
+    ('\u{415}', 'E'),
+    ('\u{41A}', 'K'),
+    ('\u{41C}', 'M'),
+    ('\u{41D}', 'H'),
+    ('\u{41E}', 'O'),
+    ('\u{420}', 'P'),
+    ('\u{421}', 'C'),
+    ('\u{422}', 'T'),
+    ('\u{425}', 'X'),
+    ('\u{430}', 'a'),
+    ('\u{435}', 'e'),
+    ('\u{43E}', 'o'),
+    ('\u{440}', 'p'),
+    ('\u{441}', 'c'),
+    ('\u{443}', 'y'),
+    ('\u{445}', 'x'),
+    ('\u{455}', 's'),
+    ('\u{456}', 'i'),
+    ('\u{458}', 'j'),
+    // Spaces and punctuation
+    ('\u{A0}', ' '),
+    ('\u{2007}', ' '),
+    ('\u{202F}', ' '),
+    ('\u{3000}', ' '),
+    ('\u{2010}', '-'),
+    ('\u{2011}', '-'),
+    ('\u{2012}', '-'),
+    ('\u{2013}', '-'),
+    ('\u{2212}', '-'),
+    ('\u{2DC}', '~'),
+    ('\u{2053}', '~'),
+    ('\u{223C}', '~'),
+    ('\u{2018}', '\''),
+    ('\u{2019}', '\''),
+    ('\u{201C}', '"'),
+    ('\u{201D}', '"'),
+    ('\u{2044}', '/'),
+    ('\u{2215}', '/'),
+    ('\u{2216}', '\\'),
+];
+
+/// The pdfTeX-compatibility primitives that XeTeX may provide, under the names
+/// that XeTeX uses for them. Most drop pdfTeX's `pdf` prefix.
+const PDFTEX_COMPAT_PRIMITIVES: &[&str] = &[
+    "strcmp",
+    "creationdate",
+    "filemoddate",
+    "filesize",
+    "mdfivesum",
+    "filedump",
+    "shellescape",
+    "elapsedtime",
+    "resettimer",
+    "randomseed",
+    "setrandomseed",
+    "uniformdeviate",
+    "normaldeviate",
+    "expanded",
+    "leftmarginkern",
+    "rightmarginkern",
+    "pdfsavepos",
+    "pdflastxpos",
+    "pdflastypos",
+    "pdfoutput",
+    "pdfpagewidth",
+    "pdfpageheight",
+];
+
+/// XeTeX's primitives for including graphics and PDF pages.
+const GRAPHICS_PRIMITIVES: &[&str] = &["XeTeXpicfile", "XeTeXpdffile", "XeTeXpdfpagecount"];
+
+/// The kinds of control sequences reported by [`Format::named_allocations`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum AllocationKind {
+    /// A `\count` register, named with `\countdef`.
+    Count,
+
+    /// A `\dimen` register, named with `\dimendef`.
+    Dimen,
+
+    /// A `\skip` register, named with `\skipdef`.
+    Skip,
+
+    /// A `\muskip` register, named with `\muskipdef`.
+    MuSkip,
+
+    /// A `\toks` register, named with `\toksdef`.
+    Toks,
+
+    /// A character code, defined with `\chardef`.
+    Char,
+
+    /// A math character code, defined with `\mathchardef`.
+    MathChar,
+
+    /// A Unicode math character code, defined with `\Umathchardef`.
+    UMathChar,
+}
+
+/// A control sequence that names a register or a numeric constant.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct NamedAllocation {
+    /// The name of the control sequence, without a leading backslash.
+    pub name: String,
+
+    /// What the control sequence refers to.
+    pub kind: AllocationKind,
+
+    /// The register number, or the value of the constant.
+    pub value: i32,
+}
+
+/// A major section of a format file, as reported to the progress callback of
+/// [`Format::parse_with_progress`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ParseStage {
+    /// The string table.
+    StringTable,
+
+    /// The dynamic memory array, `mem`.
+    Mem,
+
+    /// The equivalencies table, `eqtb`.
+    Eqtb,
+
+    /// The primitives and the control-sequence hash table.
+    Hash,
+
+    /// The font information.
+    Fonts,
+
+    /// The hyphenation exceptions.
+    Hyphenation,
+
+    /// The hyphenation pattern trie.
+    Trie,
+}
+
+/// Statistics about the parsing of a format file, as gathered by
+/// [`Format::parse_with_stats`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseStats {
+    /// The number of bytes of input that were consumed, not counting any
+    /// unexpected data after the footer.
+    pub total_bytes: usize,
+
+    /// The number of bytes in the header and the engine parameters that
+    /// follow it.
+    pub header_bytes: usize,
+
+    /// The number of bytes in each major section, in file order.
+    ///
+    /// The [`ParseStage::Hash`] section also includes the locations of
+    /// `\par` and `\write` and the primitive table, which precede the hash
+    /// table proper.
+    pub section_bytes: Vec<(ParseStage, usize)>,
+
+    /// The number of bytes in the footer.
+    pub footer_bytes: usize,
+
+    /// The number of strings in the string table.
+    pub strings: usize,
+
+    /// The number of preloaded fonts, including the `\nullfont`.
+    pub fonts: usize,
+
+    /// The number of defined control sequences.
+    pub control_sequences: usize,
+
+    /// The number of hyphenation exceptions.
+    pub hyphenation_exceptions: usize,
+
+    /// The number of entries in the hyphenation trie.
+    pub trie_entries: usize,
+
+    /// The number of hyphenation trie operations.
+    pub trie_ops: usize,
+}
+
 impl Format {
     pub fn parse(input: &[u8]) -> Result<Self> {
-        let (input, serial) = match parse_header(input) {
-            Ok(t) => t,
-            Err(NomErr::Error(inner)) => bail!("parse error: {}", inner.code.description()),
-            Err(NomErr::Failure(inner)) => bail!("parse failure: {}", inner.code.description()),
-            Err(NomErr::Incomplete(_)) => bail!("incomplete input"),
-        };
+        FormatParser::default().parse_into(input)
+    }
+
+    /// Parse a format file, tolerating a missing or corrupt footer.
+    ///
+    /// This allows data to be recovered from formats that were truncated just
+    /// before the end. Use [`Format::footer_ok`] to find out whether the
+    /// footer was actually present.
+    pub fn parse_tolerant(input: &[u8]) -> Result<Self> {
+        FormatParser::default().set_tolerant(true).parse_into(input)
+    }
+
+    /// Check whether the format file ended with the expected footer.
+    ///
+    /// This is always true unless the format was parsed with
+    /// [`Format::parse_tolerant`] or a tolerant [`FormatParser`], since
+    /// otherwise a bad footer is an error.
+    pub fn footer_ok(&self) -> bool {
+        self.footer_ok
+    }
+
+    /// Parse a format file, reporting progress as it goes.
+    ///
+    /// The *progress* callback is invoked with each [`ParseStage`] as that
+    /// section of the file finishes parsing, which is useful for showing
+    /// progress when parsing large formats.
+    pub fn parse_with_progress<F: FnMut(ParseStage)>(input: &[u8], progress: F) -> Result<Self> {
+        FormatParser::default().parse_with_progress(input, progress)
+    }
 
+    /// Parse a format file, also gathering statistics about the parse.
+    ///
+    /// This is equivalent to [`Format::parse`], but also returns a
+    /// [`ParseStats`] measuring how the input was divided up and how much was
+    /// decoded from it.
+    pub fn parse_with_stats(input: &[u8]) -> Result<(Self, ParseStats)> {
+        FormatParser::default().parse_with_stats(input)
+    }
+
+    /// Cheaply check whether some data look like the start of a format file.
+    ///
+    /// This only examines the magic number and version at the very start of
+    /// the data, without allocating anything, so it's suitable for sorting
+    /// through many files before deciding which ones to parse. Note that
+    /// compressed data, such as the gzipped formats that some TeX
+    /// distributions produce, will not be recognized: they must be
+    /// decompressed first.
+    pub fn is_probably_format(input: &[u8]) -> bool {
+        match parse_header(input) {
+            Ok((_, serial)) => serial > 0 && serial as FormatVersion <= LATEST_VERSION,
+            Err(_) => false,
+        }
+    }
+
+    /// Parse a format file extracted from a Tectonic bundle or cache.
+    ///
+    /// Tectonic doesn't add any framing of its own to the files it stores:
+    /// a format's bundle entry is the raw output of `\dump`, so this is
+    /// equivalent to [`Format::parse`]. Entries in some bundle formats are
+    /// individually gzip-compressed, though, and those are reported with a
+    /// clear error, since this crate can't decompress them.
+    pub fn parse_bundle_entry(input: &[u8]) -> Result<Self> {
+        ensure!(
+            !input.starts_with(&GZIP_MAGIC),
+            "bundle entry is gzip-compressed and must be decompressed first"
+        );
+        Format::parse(input)
+    }
+
+    /// Parse every file in the directory *dir*.
+    ///
+    /// The directory isn't searched recursively. Each file's result is
+    /// reported separately, sorted by path, so that one bad file doesn't
+    /// prevent the others from being parsed. Gzip-compressed files are
+    /// detected and reported as errors, since this crate can't decompress
+    /// them. An error is only returned if the directory itself can't be read.
+    pub fn parse_all<P: AsRef<Path>>(dir: P) -> Result<Vec<(PathBuf, Result<Format>)>> {
+        let dir = dir.as_ref();
+        let mut paths = Vec::new();
+
+        for entry in atry!(fs::read_dir(dir); ["failed to read directory `{}`", dir.display()]) {
+            let entry = atry!(entry; ["failed to read directory `{}`", dir.display()]);
+
+            if entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                paths.push(entry.path());
+            }
+        }
+
+        paths.sort();
+
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let result = fs::read(&path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))
+                    .and_then(|data| {
+                        ensure!(
+                            !data.starts_with(&GZIP_MAGIC),
+                            "`{}` is gzip-compressed and must be decompressed first",
+                            path.display()
+                        );
+                        Format::parse(&data[..])
+                    });
+                (path, result)
+            })
+            .collect())
+    }
+
+    /// Decode only the fonts of a format file.
+    ///
+    /// The sections preceding the font information are skipped over without
+    /// being materialized, so this is much faster than a full
+    /// [`Format::parse`] when the font list is all that's needed.
+    pub fn fonts_only(input: &[u8]) -> Result<Vec<fonts::Font>> {
+        let full = input;
+        check_input_length(input)?;
+        let (input, serial) = describe_nom_result(full, parse_header(input))?;
+        let engine = Engine::new_for_version(serial as FormatVersion)?;
+        let (_remainder, fonts) = describe_nom_result(full, parse_fonts_only(&engine, input))?;
+        Ok(fonts.fonts)
+    }
+
+    /// Decode only the string table of a format file.
+    ///
+    /// The string table comes right after the fixed header fields, so parsing
+    /// stops there, and this is much faster than a full [`Format::parse`] when
+    /// only the strings are needed.
+    pub fn parse_strings_only(input: &[u8]) -> Result<stringtable::StringTable> {
+        let full = input;
+        check_input_length(input)?;
+        let (input, serial) = describe_nom_result(full, parse_header(input))?;
         let engine = Engine::new_for_version(serial as FormatVersion)?;
+        let (input, _hash_high) = describe_nom_result(full, parse_parameters(&engine, input))?;
+        let (_remainder, strings) =
+            describe_nom_result(full, stringtable::StringTable::parse(input))?;
+        Ok(strings)
+    }
+
+    /// Check whether this format shares its engine layout with another.
+    ///
+    /// Formats must be compatible in this sense before it makes sense to
+    /// compare their contents: the same eqtb location can mean entirely
+    /// different things in two engine versions.
+    pub fn version_compatible_with(&self, other: &Format) -> bool {
+        self.engine.version == other.engine.version
+            && ["EQTB_SIZE", "HASH_PRIME", "MEM_TOP"]
+                .iter()
+                .all(|s| self.engine.symbols.lookup(s) == other.engine.symbols.lookup(s))
+    }
+
+    /// Summarize the differences between this format and another one,
+    /// stopping after *limit* differences have been found.
+    ///
+    /// This compares the string tables, the integer and dimension parameters,
+    /// the category codes, and the preloaded fonts, in that order. It's meant
+    /// for quickly checking whether two formats are basically the same.
+    ///
+    /// An error is returned if the formats aren't
+    /// [compatible](Format::version_compatible_with), since their contents
+    /// can't be meaningfully compared.
+    pub fn diff_summary(&self, other: &Format, limit: usize) -> Result<DiffSummary> {
+        if !self.version_compatible_with(other) {
+            bail!(
+                "cannot compare formats with incompatible engine layouts (versions {} and {})",
+                self.engine.version,
+                other.engine.version
+            );
+        }
+
+        let mut summary = DiffSummary::new(limit);
+        self.diff_into(other, &mut summary);
+        Ok(summary)
+    }
+
+    /// Compute checksums of the main sections of this format.
+    ///
+    /// See [`SectionCrcs`] for a description of what goes into each checksum.
+    pub fn section_crcs(&self) -> SectionCrcs {
+        let mut strings = Crc32::new();
+
+        for c in self.strings.pool() {
+            strings.update(&c.to_be_bytes());
+        }
+
+        for start in self.strings.starts() {
+            strings.update_i32(*start as i32);
+        }
+
+        let mut eqtb = Crc32::new();
+        eqtb.update(self.eqtb.as_bytes());
+
+        let mut hash = Crc32::new();
+        hash.update(self.cshash.as_bytes());
+
+        let mut fonts = Crc32::new();
+
+        for word in &self.fonts.info {
+            fonts.update_i64(*word);
+        }
+
+        for f in &self.fonts.fonts {
+            fonts.update_i64(f.check);
+
+            for v in [
+                f.size.as_sp(),
+                f.design_size.as_sp(),
+                f.params,
+                f.hyphen_char,
+                f.skew_char,
+                f.name,
+                f.area,
+            ]
+            .iter()
+            {
+                fonts.update_i32(*v);
+            }
+
+            fonts.update_i16(f.bc);
+            fonts.update_i16(f.ec);
+
+            for v in [
+                f.char_base,
+                f.width_base,
+                f.height_base,
+                f.depth_base,
+                f.italic_base,
+                f.lig_kern_base,
+                f.kern_base,
+                f.exten_base,
+                f.param_base,
+                f.glue,
+                f.bchar_label,
+                f.bchar,
+                f.false_bchar,
+            ]
+            .iter()
+            {
+                fonts.update_i32(*v);
+            }
+        }
+
+        let mut hyphenation = Crc32::new();
+        let exc = &self.hyph_exceptions;
+        hyphenation.update_i32(exc.count);
+        hyphenation.update_i32(exc.next);
+
+        for ((link, word), list) in exc.link.iter().zip(&exc.word).zip(&exc.list) {
+            hyphenation.update(&link.to_be_bytes());
+            hyphenation.update_i32(*word);
+            hyphenation.update_i32(*list);
+        }
+
+        let mut trie = Crc32::new();
+        let t = &self.trie;
+        trie.update_i32(t.max);
+        trie.update_i32(t.hyph_start);
+        t.trl.iter().for_each(|v| trie.update_i32(*v));
+        t.tro.iter().for_each(|v| trie.update_i32(*v));
+        t.trc.iter().for_each(|v| trie.update(&v.to_be_bytes()));
+        trie.update_i32(t.max_hyph_char);
+        trie.update_i32(t.op_ptr);
+        t.hyf_distance.iter().for_each(|v| trie.update_i16(*v));
+        t.hyf_num.iter().for_each(|v| trie.update_i16(*v));
+        t.hyf_next
+            .iter()
+            .for_each(|v| trie.update(&v.to_be_bytes()));
+        t.trie_used.iter().for_each(|v| trie.update_i32(*v));
+        t.op_start.iter().for_each(|v| trie.update_i32(*v));
+
+        SectionCrcs {
+            strings: strings.finish(),
+            eqtb: eqtb.finish(),
+            hash: hash.finish(),
+            fonts: fonts.finish(),
+            hyphenation: hyphenation.finish(),
+            trie: trie.finish(),
+        }
+    }
+
+    fn diff_into(&self, other: &Format, summary: &mut DiffSummary) {
+        let ours: BTreeSet<&str> = self.strings.strings.iter().map(|s| s.as_ref()).collect();
+        let theirs: BTreeSet<&str> = other.strings.strings.iter().map(|s| s.as_ref()).collect();
+
+        for s in ours.difference(&theirs) {
+            if !summary.record(DiffCategory::Strings, format!("only in first: {:?}", s)) {
+                return;
+            }
+        }
+
+        for s in theirs.difference(&ours) {
+            if !summary.record(DiffCategory::Strings, format!("only in second: {:?}", s)) {
+                return;
+            }
+        }
+
+        for par in &self.engine.int_pars {
+            let a = self.int_parameter(par);
+            let b = other.int_parameter_named(par.name());
+
+            if a != b
+                && !summary.record(
+                    DiffCategory::IntParameters,
+                    format!("{}: {} => {}", par.name(), a, b),
+                )
+            {
+                return;
+            }
+        }
+
+        for par in &self.engine.dimen_pars {
+            let a = self.dimen_parameter(par);
+            let b = other.dimen_parameter_named(par.name());
+
+            if a != b
+                && !summary.record(
+                    DiffCategory::DimenParameters,
+                    format!("{}: {} => {}", par.name(), a, b),
+                )
+            {
+                return;
+            }
+        }
+
+        for chr in valid_usvs() {
+            let a = self.eqtb_catcode(chr).ok();
+            let b = other.eqtb_catcode(chr).ok();
+
+            if a != b {
+                let show = |c: Option<CatCode>| c.map_or("?", |c| c.abbrev());
+                let desc = format!("{}: {} => {}", fmt_usv(chr), show(a), show(b));
+
+                if !summary.record(DiffCategory::Catcodes, desc) {
+                    return;
+                }
+            }
+        }
+
+        let n_fonts = self.fonts.fonts.len().max(other.fonts.fonts.len());
+
+        for i in 0..n_fonts {
+            let describe = |fmt: &Format| {
+                fmt.fonts.fonts.get(i).map_or_else(
+                    || "[none]".to_owned(),
+                    |f| format!("{} at {}", fmt.fmt_str_pointer(f.name), f.size),
+                )
+            };
+
+            let (a, b) = (describe(self), describe(other));
+
+            if a != b && !summary.record(DiffCategory::Fonts, format!("font {}: {} => {}", i, a, b))
+            {
+                return;
+            }
+        }
+    }
+
+    /// Check the internal consistency of the decoded format.
+    ///
+    /// The parser validates individual values as it goes, but some invariants
+    /// span multiple fields. This method checks them all at once, returning an
+    /// error describing the first violation found. It is intended for
+    /// regression-testing the parser over a corpus of format files.
+    ///
+    /// There's no need to check the grouping level here: the engine refuses to
+    /// `\dump` inside a group, so a format is always saved at the bottom level,
+    /// and neither the save stack nor `\currentgrouplevel` is recorded in it.
+    /// For the same reason there's nothing to check for pending
+    /// `\afterassignment` or `\aftergroup` tokens: the former is held in an
+    /// engine variable that isn't saved, and the latter live on the save stack.
+    pub fn self_check(&self) -> Result<()> {
+        let n_strings = self.strings.len() as i32;
+        let max_sp = n_strings + TOO_BIG_CHAR - 1;
+
+        ensure!(self.footer_ok, "the format footer is missing or corrupt");
+
+        self.mem.validate()?;
+
+        let last_nodes = self.last_node_state();
+        ensure!(
+            last_nodes.is_clean(),
+            "the format was dumped with nonempty page lists: {:?}",
+            last_nodes
+        );
+
+        ensure!(
+            self.trailing_bytes == 0,
+            "{} bytes of unexpected data after the format footer",
+            self.trailing_bytes
+        );
+
+        // Hyphenation exceptions
+
+        let n_words = self.hyph_exceptions.slots_used();
+        ensure!(
+            n_words == self.hyph_exceptions.count as usize,
+            "expected {} hyphenation exceptions but decoded {}",
+            self.hyph_exceptions.count,
+            n_words
+        );
 
-        match parse_body(engine, input) {
-            Ok((_remainder, result)) => Ok(result),
-            Err(NomErr::Error(inner)) => bail!("parse error: {}", inner.code.description()),
-            Err(NomErr::Failure(inner)) => bail!("parse failure: {}", inner.code.description()),
-            Err(NomErr::Incomplete(_)) => bail!("incomplete input"),
+        for (i, w) in self.hyph_exceptions.word.iter().enumerate() {
+            ensure!(
+                *w <= max_sp,
+                "hyphenation exception slot {} refers to nonexistent string {}",
+                i,
+                w
+            );
         }
+
+        // Trie
+
+        let total_used: i32 = self.trie.trie_used.iter().sum();
+        ensure!(
+            total_used == self.trie.op_ptr,
+            "per-language trie op counts sum to {}, but trie_op_ptr is {}",
+            total_used,
+            self.trie.op_ptr
+        );
+        ensure!(
+            self.trie.trl.len() == self.trie.max as usize + 1,
+            "trie is missing entries"
+        );
+
+        // Fonts
+
+        ensure!(!self.fonts.fonts.is_empty(), "the null font is missing");
+        let fmem_ptr = self.fonts.info.len() as i32;
+
+        for (i, font) in self.fonts.fonts.iter().enumerate() {
+            ensure!(
+                font.name <= max_sp && font.area <= max_sp,
+                "font {} refers to a nonexistent name or area string",
+                i
+            );
+            ensure!(
+                font.param_base + font.params < fmem_ptr,
+                "font {} parameters extend past the end of font_info",
+                i
+            );
+        }
+
+        Ok(())
     }
 
     pub fn dump_string_table<W: Write>(&self, stream: &mut W) -> Result<()> {
-        for sp in self.strings.all_sps() {
-            let value = self.strings.lookup(sp);
-            writeln!(stream, "{} = \"{}\"", sp, value)?;
+        self.dump_string_table_with(stream, |sp, value| format!("{} = \"{}\"", sp, value))
+    }
+
+    /// Dump the string table with a custom per-string formatter.
+    ///
+    /// The formatter is called with each string pointer and its value, and
+    /// should return the text of the output line for that string, without a
+    /// trailing newline.
+    pub fn dump_string_table_with<W, F>(&self, stream: &mut W, mut formatter: F) -> Result<()>
+    where
+        W: Write,
+        F: FnMut(stringtable::StrPointer, &str) -> String,
+    {
+        for (sp, value) in self.strings_iter() {
+            writeln!(stream, "{}", formatter(sp, value))?;
         }
 
         Ok(())
     }
 
     pub fn dump_actives<W: Write>(&self, stream: &mut W) -> Result<()> {
+        self.dump_actives_filtered(stream, |_, _| true)
+    }
+
+    /// Dump the definitions of the active characters that satisfy a predicate.
+    ///
+    /// The predicate is called with each defined active character and its
+    /// eqtb entry, and only the characters for which it returns true are
+    /// dumped. For instance, [`eqtb::EqtbEntry::classify`] can be used to
+    /// select only the characters defined as macros.
+    pub fn dump_actives_filtered<W, F>(&self, stream: &mut W, pred: F) -> Result<()>
+    where
+        W: Write,
+        F: Fn(char, &eqtb::EqtbEntry) -> bool,
+    {
         let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
 
         for chr in valid_usvs() {
             let entry = self.eqtb_active(chr);
 
-            if entry.ty == undefined_cs_cmd {
-                continue;
-            }
+            if entry.ty == undefined_cs_cmd {
+                continue;
+            }
+
+            // `valid_usvs` skips the surrogates, so this always succeeds.
+            let c = char::from_u32(chr as u32).unwrap();
+
+            if !pred(c, &entry) {
+                continue;
+            }
+
+            let cur_cat = self.eqtb_catcode(chr)?;
+            let cmd_desc = self.engine.commands.describe(entry.ty, entry.value);
+
+            writeln!(
+                stream,
+                "{} ({}) => {}",
+                fmt_usv(chr),
+                cur_cat.abbrev(),
+                cmd_desc
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn dump_catcodes<W: Write>(&self, stream: &mut W) -> Result<()> {
+        self.dump_catcodes_with(stream, CatcodeDumpOptions::default())
+    }
+
+    /// Dump the category codes, with options.
+    ///
+    /// The characters are grouped by category code, and runs of consecutive
+    /// characters with the same code are shown as ranges.
+    pub fn dump_catcodes_with<W: Write>(
+        &self,
+        stream: &mut W,
+        options: CatcodeDumpOptions,
+    ) -> Result<()> {
+        let mut blocks = vec![Vec::new(); 16];
+        let mut cur_run: Option<(CatCode, i32, i32)> = None;
+        let mut n_hidden = 0;
+
+        for chr in valid_usvs() {
+            let cat = self.eqtb_catcode(chr)?;
+
+            if !options.show_defaults && cat == CatCode::initial_for(chr) {
+                n_hidden += 1;
+
+                if let Some((cat, start, prev)) = cur_run.take() {
+                    blocks[cat as usize].push((start, prev));
+                }
+
+                continue;
+            }
+
+            cur_run = match cur_run {
+                Some((run_cat, start, _)) if run_cat == cat => Some((cat, start, chr)),
+
+                Some((run_cat, start, prev)) => {
+                    blocks[run_cat as usize].push((start, prev));
+                    Some((cat, chr, chr))
+                }
+
+                None => Some((cat, chr, chr)),
+            };
+        }
+
+        if let Some((cat, start, prev)) = cur_run {
+            blocks[cat as usize].push((start, prev));
+        }
+
+        let mut first = true;
+
+        for cat in 0..16 {
+            if !options.show_defaults && blocks[cat as usize].is_empty() {
+                continue;
+            }
+
+            if !first {
+                writeln!(stream)?;
+            }
+
+            first = false;
+            writeln!(stream, "{}:", CatCode::from_i32(cat).unwrap().description())?;
+
+            for block in &blocks[cat as usize] {
+                let (start, end) = *block;
+
+                if end == start {
+                    writeln!(stream, "    {}", fmt_usv(start))?;
+                } else {
+                    writeln!(stream, "    {} - {}", fmt_usv(start), fmt_usv(end))?;
+                }
+            }
+        }
+
+        if !options.show_defaults {
+            if !first {
+                writeln!(stream)?;
+            }
+
+            writeln!(
+                stream,
+                "({} characters with their initial category codes not shown)",
+                n_hidden
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Find out which category codes are in use: element *n* of the result is
+    /// true if at least one character has category code *n*.
+    ///
+    /// Characters with invalid category codes are ignored; use
+    /// [`Format::dump_catcodes`] to find those.
+    pub fn catcodes_present(&self) -> [bool; 16] {
+        let mut present = [false; 16];
+
+        for chr in valid_usvs() {
+            if let Ok(cat) = self.eqtb_catcode(chr) {
+                present[cat as usize] = true;
+            }
+        }
+
+        present
+    }
+
+    /// Check whether every character from *start* to *end*, inclusive, has
+    /// the category code that it has in a freshly initialized engine.
+    ///
+    /// This is useful for finding out which Unicode blocks a format has
+    /// configured. Surrogate code points, and values outside the range of
+    /// Unicode, are skipped. A character whose stored category code is
+    /// invalid doesn't have its default category code.
+    pub fn is_block_default_catcode(&self, start: i32, end: i32) -> bool {
+        (start.max(0)..=end.min(MAX_USV - 1))
+            .filter(|c| !(0xD800..0xE000).contains(c))
+            .all(|c| {
+                let default = self.engine.default_catcode(c);
+                matches!(self.eqtb_catcode(c), Ok(cat) if cat == default)
+            })
+    }
+
+    /// Find the category-code assignments that are the fingerprints of
+    /// verbatim machinery.
+    ///
+    /// This reports the characters with a conventional special category
+    /// code (see [`CatCode::conventional_special`]) that have been made
+    /// "other", along with any characters that have been made active. Results
+    /// are sorted by character.
+    pub fn verbatim_catcode_sets(&self) -> catcodes::VerbatimCatcodes {
+        let mut result = catcodes::VerbatimCatcodes::default();
+
+        for chr in valid_usvs() {
+            let cat = match self.eqtb_catcode(chr) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+
+            let conventional = CatCode::conventional_special(chr);
+            let c = char::from_u32(chr as u32).unwrap();
+
+            match (cat, conventional) {
+                (CatCode::Other, Some(special)) => result.othered_specials.push((c, special)),
+                (CatCode::Active, Some(CatCode::Active)) => {}
+                (CatCode::Active, _) => result.actives.push(c),
+                _ => {}
+            }
+        }
+
+        result
+    }
+
+    /// Get the sorted names of the control sequences and active characters
+    /// that have definitions.
+    ///
+    /// Control sequence names are prefixed with the format's `\escapechar`,
+    /// if it is a valid character, but are otherwise given verbatim, e.g.
+    /// `\relax`. Active characters are rendered as by [`fmt_usv`], e.g.
+    /// `~ (0x00007e)`, so they stay distinct from the single-character control
+    /// sequences even when `\escapechar` is -1. The null control sequence has
+    /// no name and is omitted.
+    ///
+    /// The list contains no duplicates. If a name is reachable from more than
+    /// one place in the engine's tables, the active definition — the one
+    /// that looking up the name finds — decides whether it is listed.
+    pub fn control_sequence_names(&self) -> Vec<String> {
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+
+        let cseqs = self.cseqs().map(|(name, _)| name).filter(|name| {
+            matches!(
+                self.cs_pointer(name),
+                Some(ptr) if !name.is_empty() && self.eqtb.decode(ptr).ty != undefined_cs_cmd
+            )
+        });
+        let actives = self.active_characters().into_iter().map(|(c, _)| c);
+
+        merge_control_sequence_names(cseqs, actives, self.int_parameter_named("escape_char"))
+    }
+
+    pub fn dump_cseqs<W: Write>(&self, stream: &mut W, extended: bool) -> Result<()> {
+        self.dump_cseq_entries(stream, self.cseqs(), extended)
+    }
+
+    /// Extract the introspectable data of this format into a
+    /// [`snapshot::Snapshot`].
+    ///
+    /// Control sequence meanings are described as by
+    /// [`Format::dump_cseqs`], and the control sequences are sorted by name.
+    pub fn snapshot(&self) -> snapshot::Snapshot {
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+
+        let catcodes = valid_usvs()
+            .filter_map(|chr| {
+                let cat = self.eqtb_catcode(chr).ok()?;
+
+                if cat == CatCode::initial_for(chr) {
+                    None
+                } else {
+                    char::from_u32(chr as u32).map(|c| (c, cat))
+                }
+            })
+            .collect();
+
+        let mut control_sequences: Vec<_> = self
+            .cseqs()
+            .filter_map(|(name, ptr)| {
+                let entry = self.eqtb.decode(ptr);
+
+                if entry.ty == undefined_cs_cmd {
+                    None
+                } else {
+                    Some((name, self.engine.commands.describe(entry.ty, entry.value)))
+                }
+            })
+            .collect();
+        control_sequences.sort();
+        control_sequences.dedup();
+
+        let actives = self
+            .active_characters()
+            .into_iter()
+            .map(|(c, entry)| (c, self.engine.commands.describe(entry.ty, entry.value)))
+            .collect();
+
+        let fonts = self
+            .fonts
+            .fonts
+            .iter()
+            .map(|font| snapshot::SnapshotFont {
+                name: self.fmt_str_pointer(font.name),
+                area: self.fmt_str_pointer(font.area),
+                size: font.size,
+                design_size: font.design_size,
+            })
+            .collect();
+
+        snapshot::Snapshot {
+            format_version: self.engine.version,
+            strings: self.strings.strings.clone(),
+            catcodes,
+            control_sequences,
+            actives,
+            fonts,
+        }
+    }
+
+    /// Write a compact snapshot of this format's introspectable data to
+    /// *stream*.
+    ///
+    /// Use [`snapshot::Snapshot::import_snapshot`] to load it again.
+    pub fn export_snapshot<W: Write>(&self, stream: &mut W) -> Result<()> {
+        self.snapshot().export_snapshot(stream)
+    }
+
+    fn dump_cseq_entries<W, I>(&self, stream: &mut W, cseqs: I, extended: bool) -> Result<()>
+    where
+        W: Write,
+        I: Iterator<Item = (String, EqtbPointer)>,
+    {
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+
+        for (name, ptr) in cseqs {
+            let entry = self.eqtb.decode(ptr);
+
+            if entry.ty == undefined_cs_cmd {
+                continue;
+            }
+
+            let cs_desc = self.fmt_csname(&name);
+
+            let (cmd_desc, extended) = if extended {
+                self.engine
+                    .commands
+                    .describe_extended(entry.ty, entry.value, self)
+            } else {
+                (self.engine.commands.describe(entry.ty, entry.value), None)
+            };
+
+            writeln!(stream, "{} => {}", cs_desc, cmd_desc)?;
+
+            if let Some(e) = extended {
+                writeln!(stream, "--------\n{}\n--------", e)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dump the values of the token-list parameters (`\everypar`, `\output`,
+    /// etc.).
+    ///
+    /// This includes the e-TeX and XeTeX extension parameters such as
+    /// `\everyeof`, but only those that are defined by the engine version
+    /// associated with this format. This covers the whole token-list region
+    /// of the eqtb: `\everyeof` is e-TeX's only token-list parameter, and the
+    /// working state of e-TeX commands like `\scantokens` and `\detokenize`
+    /// lives outside of the eqtb and isn't saved in formats.
+    pub fn dump_toks_parameters<W: Write>(&self, stream: &mut W) -> Result<()> {
+        for par in &self.engine.local_pars {
+            if par.kind() != LocalParKind::TokenList {
+                continue;
+            }
+
+            let name = format!("\\{}", par.primitive_name());
+            let p = self.eqtb_local(&par.symbol_name()).value;
+
+            if p == TEX_NULL {
+                writeln!(stream, "{} => [empty]", name)?;
+            } else {
+                writeln!(
+                    stream,
+                    "{} =>\n--------\n{}--------",
+                    name,
+                    self.fmt_toklist(self.mem.decode_toklist(p).1, false)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the job startup tokens: the contents of `\everyjob`, which TeX
+    /// inserts at the start of every job run with this format.
+    ///
+    /// Formats often use this to print a banner, so it's a good way to
+    /// identify what a format does when it's loaded.
+    pub fn every_job(&self) -> Vec<Token> {
+        self.token_list(self.eqtb_local("LOCAL__every_job").value)
+    }
+
+    /// Dump the job startup tokens, `\everyjob`.
+    pub fn dump_every_job<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let p = self.eqtb_local("LOCAL__every_job").value;
+
+        if p == TEX_NULL {
+            writeln!(stream, "[empty]")?;
+        } else {
+            write!(
+                stream,
+                "{}",
+                self.fmt_toklist(self.mem.decode_toklist(p).1, false)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Dump the preloaded fonts.
+    pub fn dump_fonts<W: Write>(&self, stream: &mut W) -> Result<()> {
+        for (i, font) in self.fonts.fonts.iter().enumerate() {
+            writeln!(
+                stream,
+                "{}: {}{} at {} (design size {})",
+                i,
+                self.fmt_str_pointer(font.area),
+                self.fmt_str_pointer(font.name),
+                font.size,
+                font.design_size
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Dump the `\count`, `\dimen`, `\skip`, and `\toks` registers that
+    /// have non-default values.
+    pub fn dump_registers<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let all = 0..MAX_REG_NUM + 1;
+
+        for (n, v) in self.count_registers_in(all.clone()) {
+            if v != 0 {
+                writeln!(stream, "\\count{} = {}", n, v)?;
+            }
+        }
+
+        for (n, v) in self.dimen_registers_in(all.clone()) {
+            if v.as_sp() != 0 {
+                writeln!(stream, "\\dimen{} = {}", n, v)?;
+            }
+        }
+
+        for (n, g) in self.skip_registers_in(all.clone()) {
+            if g != mem::GlueSpec::default() {
+                writeln!(
+                    stream,
+                    "\\skip{} = {} plus {} (order {}) minus {} (order {})",
+                    n, g.width, g.stretch, g.stretch_order, g.shrink, g.shrink_order
+                )?;
+            }
+        }
+
+        for (n, p) in self.registers_in(all, "TOKS_BASE", "TOK_VAL", TEX_NULL) {
+            if p != TEX_NULL {
+                writeln!(
+                    stream,
+                    "\\toks{} =>\n--------\n{}--------",
+                    n,
+                    self.fmt_toklist(self.mem.decode_toklist(p).1, false)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dump only the settings that differ from those of a freshly initialized
+    /// engine: in effect, a diff against INITEX.
+    ///
+    /// This covers the category and other character codes, the integer,
+    /// dimension, and glue parameters, and the registers. The output is much
+    /// more concise than the full dumps, and shows what a format actually
+    /// changed.
+    pub fn dump_nondefault<W: Write>(&self, stream: &mut W) -> Result<()> {
+        type CodeTable = (&'static str, &'static str, fn(i32) -> i32);
+
+        const CODE_TABLES: &[CodeTable] = &[
+            ("CAT_CODE_BASE", "catcode", |c| {
+                CatCode::initial_for(c) as i32
+            }),
+            ("LC_CODE_BASE", "lccode", codes::initial_lc_code),
+            ("UC_CODE_BASE", "uccode", codes::initial_uc_code),
+            ("SF_CODE_BASE", "sfcode", codes::initial_sf_code),
+            ("DEL_CODE_BASE", "delcode", codes::initial_del_code),
+        ];
+
+        for (base, name, initial) in CODE_TABLES {
+            let base = self.engine.symbols.lookup(base) as EqtbPointer;
+
+            for c in valid_usvs() {
+                let value = self.eqtb.decode(base + c).value;
+
+                if value != initial(c) {
+                    writeln!(stream, "\\{} {} = {}", name, fmt_usv(c), value)?;
+                }
+            }
+        }
+
+        for (c, code) in self.u_math_codes() {
+            writeln!(
+                stream,
+                "\\Umathcode {} = class {}, family {}, slot 0x{:x}",
+                fmt_usv(c),
+                code.class,
+                code.family,
+                code.slot
+            )?;
+        }
+
+        for par in &self.engine.int_pars {
+            let value = self.int_parameter(par);
+
+            if value != par.initial_value() {
+                writeln!(stream, "{} = {}", par.name(), value)?;
+            }
+        }
+
+        for (name, value) in self.dimen_parameters() {
+            if value.as_sp() != 0 {
+                writeln!(stream, "{} = {}", name, value)?;
+            }
+        }
+
+        for par in &self.engine.glue_pars {
+            let g = self.glue_parameter_named(par.name());
+
+            if g != mem::GlueSpec::default() {
+                writeln!(
+                    stream,
+                    "{} = {} plus {} (order {}) minus {} (order {})",
+                    par.name(),
+                    g.width,
+                    g.stretch,
+                    g.stretch_order,
+                    g.shrink,
+                    g.shrink_order
+                )?;
+            }
+        }
+
+        self.dump_registers(stream)
+    }
+
+    /// Dump the hyphenation exceptions, and the number of pattern ops used by
+    /// each language.
+    pub fn dump_hyphenation<W: Write>(&self, stream: &mut W) -> Result<()> {
+        for h in 0..hyphenation::HYPH_SIZE {
+            let mut text = match self.hyph_exceptions.word_text(h, &self.strings) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            // The last character of the word is its language.
+            let lang = text.pop().map_or(0, |c| c as u32);
+
+            let mut positions = Vec::new();
+            let mut p = self.hyph_exceptions.list[h];
+
+            while p != TEX_NULL && positions.len() <= text.len() {
+                let (pos, next) = self.mem.decode_toklist(p);
+                positions.push(pos as usize);
+                p = next;
+            }
+
+            let mut hyphenated = String::new();
+            let mut n = 0;
+
+            for c in text.chars() {
+                hyphenated.push(c);
+                n += c.len_utf16();
+
+                if positions.contains(&n) {
+                    hyphenated.push('-');
+                }
+            }
+
+            writeln!(stream, "[{}] {}", lang, hyphenated)?;
+        }
+
+        for (lang, used) in self.trie.trie_used.iter().enumerate() {
+            if *used != 0 {
+                writeln!(stream, "language {}: {} pattern ops", lang, used)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dump the primitives defined in the format, sorted by name.
+    pub fn dump_primitives<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let mut prims: Vec<_> = self
+            .primitives
+            .iter()
+            .flat_map(|(code, names)| names.iter().map(move |name| (code, name)))
+            .collect();
+        prims.sort_by_key(|(code, name)| (*name, *code));
+
+        for ((cmd, chr), name) in prims {
+            writeln!(stream, "{} => {}/{}", self.fmt_csname(name), cmd, chr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a report combining several of the individual dumps.
+    ///
+    /// The selected sections are written in a fixed order, each one preceded
+    /// by a header line of the form `===== strings =====`. The headers won't
+    /// change between releases, so that reports can be diffed.
+    pub fn dump_report<W: Write>(&self, stream: &mut W, sections: DumpSections) -> Result<()> {
+        let mut first = true;
+
+        let mut header = |stream: &mut W, section: DumpSections, name: &str| -> Result<bool> {
+            if !sections.contains(section) {
+                return Ok(false);
+            }
+
+            if !first {
+                writeln!(stream)?;
+            }
+
+            first = false;
+            writeln!(stream, "===== {} =====", name)?;
+            Ok(true)
+        };
+
+        if header(stream, DumpSections::EVERY_JOB, "job startup tokens")? {
+            self.dump_every_job(stream)?;
+        }
+
+        if header(stream, DumpSections::STRINGS, "strings")? {
+            self.dump_string_table(stream)?;
+        }
+
+        if header(stream, DumpSections::CATCODES, "catcodes")? {
+            self.dump_catcodes(stream)?;
+        }
+
+        if header(stream, DumpSections::ACTIVES, "actives")? {
+            self.dump_actives(stream)?;
+        }
+
+        if header(stream, DumpSections::FONTS, "fonts")? {
+            self.dump_fonts(stream)?;
+        }
+
+        if header(stream, DumpSections::REGISTERS, "registers")? {
+            self.dump_registers(stream)?;
+        }
+
+        if header(stream, DumpSections::HYPHENATION, "hyphenation")? {
+            self.dump_hyphenation(stream)?;
+        }
+
+        if header(stream, DumpSections::PRIMITIVES, "primitives")? {
+            self.dump_primitives(stream)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a complete report on the format in a stable, fully ordered form.
+    ///
+    /// This is intended for golden-file tests: the output depends only on the
+    /// contents of the format, so it's byte-for-byte reproducible across runs
+    /// and platforms. It consists of all of the sections of
+    /// [`Format::dump_report`], followed by the token-list parameters and then
+    /// the defined control sequences with their extended descriptions, sorted
+    /// by name rather than by their positions in the hash table.
+    pub fn dump_canonical<W: Write>(&self, stream: &mut W) -> Result<()> {
+        self.dump_report(stream, DumpSections::ALL)?;
+
+        writeln!(stream, "\n===== token-list parameters =====")?;
+        self.dump_toks_parameters(stream)?;
+
+        let mut cseqs: Vec<_> = self.cseqs().collect();
+        cseqs.sort();
+
+        writeln!(stream, "\n===== control sequences =====")?;
+        self.dump_cseq_entries(stream, cseqs.into_iter(), true)
+    }
+
+    /// Get the active characters that have definitions, along with their
+    /// eqtb entries.
+    pub fn active_characters(&self) -> Vec<(char, eqtb::EqtbEntry)> {
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+
+        valid_usvs()
+            .map(|c| (c, self.eqtb_active(c)))
+            .filter(|(_, entry)| entry.ty != undefined_cs_cmd)
+            .filter_map(|(c, entry)| char::from_u32(c as u32).map(|c| (c, entry)))
+            .collect()
+    }
+
+    /// Find all of the control sequences that name registers or numeric
+    /// constants.
+    ///
+    /// These are the control sequences defined with commands like `\countdef`
+    /// and `\chardef`, which packages use to allocate registers. Control
+    /// sequences `\let` to primitives like `\count` aren't included. The
+    /// results are sorted by kind and then by value.
+    pub fn named_allocations(&self) -> Vec<NamedAllocation> {
+        let cmd = |s: &str| self.engine.symbols.lookup(s) as CommandCode;
+        let sym = |s: &str| self.engine.symbols.lookup(s) as i32;
+
+        // The commands and eqtb regions of the low-numbered registers.
+        let regions = [
+            ("ASSIGN_INT", "COUNT_BASE", AllocationKind::Count),
+            ("ASSIGN_DIMEN", "SCALED_BASE", AllocationKind::Dimen),
+            ("ASSIGN_GLUE", "SKIP_BASE", AllocationKind::Skip),
+            ("ASSIGN_MU_GLUE", "MU_SKIP_BASE", AllocationKind::MuSkip),
+            ("ASSIGN_TOKS", "TOKS_BASE", AllocationKind::Toks),
+        ];
+
+        let sa_kinds = [
+            AllocationKind::Count,
+            AllocationKind::Dimen,
+            AllocationKind::Skip,
+            AllocationKind::MuSkip,
+        ];
+
+        let mut result: Vec<_> = self
+            .cseqs()
+            .filter_map(|(name, p)| {
+                let entry = self.eqtb.decode(p);
+
+                let (kind, value) = match entry.classify(&self.engine) {
+                    eqtb::EntryKind::CharDef => (AllocationKind::Char, entry.value),
+                    eqtb::EntryKind::MathCharDef if entry.ty == cmd("MATH_GIVEN") => {
+                        (AllocationKind::MathChar, entry.value)
+                    }
+                    eqtb::EntryKind::MathCharDef => (AllocationKind::UMathChar, entry.value),
+                    eqtb::EntryKind::Register
+                        if entry.ty == cmd("REGISTER") || entry.ty == cmd("TOKS_REGISTER") =>
+                    {
+                        // A high-numbered register, living in a sparse array.
+                        let n = self.mem.sa_num(entry.value)?;
+                        let t = base::memword_read_b16_s1(&self.mem.mem[..], entry.value) / 64;
+
+                        let kind = if entry.ty == cmd("TOKS_REGISTER") {
+                            AllocationKind::Toks
+                        } else {
+                            *sa_kinds.get(t as usize)?
+                        };
+
+                        (kind, n)
+                    }
+                    eqtb::EntryKind::Register => {
+                        let (_, base, kind) =
+                            regions.iter().find(|(c, _, _)| entry.ty == cmd(c))?;
+                        let n = entry.value - sym(base);
+
+                        if n < 0 || n as usize >= NUMBER_REGS {
+                            return None;
+                        }
+
+                        (*kind, n)
+                    }
+                    _ => return None,
+                };
+
+                Some(NamedAllocation { name, kind, value })
+            })
+            .collect();
+
+        result.sort_by(|a, b| (a.kind, a.value, &a.name).cmp(&(b.kind, b.value, &b.name)));
+        result
+    }
+
+    /// Find places where an active character and a control sequence with a
+    /// visually confusable name are both defined, such as an active `~` along
+    /// with `\∼` (a tilde operator), or an active Cyrillic `о` along with
+    /// `\o`.
+    ///
+    /// Names are compared after folding them as described in
+    /// [`confusable_skeleton`], so exact matches like an active `~` along
+    /// with `\~` are found too. Each result gives the active character and the
+    /// name of the confusable control sequence, without a leading backslash.
+    /// The results are sorted.
+    pub fn active_cs_report(&self) -> Vec<(char, String)> {
+        let actives = self.active_characters();
+
+        if actives.is_empty() {
+            return Vec::new();
+        }
+
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+        let mut by_skeleton: HashMap<String, Vec<String>> = HashMap::new();
+
+        for (name, ptr) in self.cseqs() {
+            if !name.is_empty() && self.eqtb.decode(ptr).ty != undefined_cs_cmd {
+                by_skeleton
+                    .entry(confusable_skeleton(&name))
+                    .or_default()
+                    .push(name);
+            }
+        }
+
+        let mut result = Vec::new();
+
+        for (c, _) in actives {
+            let mut buf = [0; 4];
+
+            if let Some(names) = by_skeleton.get(&confusable_skeleton(c.encode_utf8(&mut buf))) {
+                result.extend(names.iter().map(|name| (c, name.clone())));
+            }
+        }
+
+        result.sort();
+        result.dedup();
+        result
+    }
+
+    /// Decode a reference-counted token list into its tokens.
+    ///
+    /// The pointer should refer to the head of the list, which holds its
+    /// reference count, as is the case for token-list parameters, token
+    /// registers, and macro definitions.
+    pub fn token_list(&self, p: mem::MemPointer) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        if p == TEX_NULL {
+            return tokens;
+        }
+
+        let mut p = self.mem.decode_toklist(p).1;
+
+        while p != TEX_NULL {
+            let (value, next) = self.mem.decode_toklist(p);
+            tokens.push(Token::from(value));
+            p = next;
+        }
+
+        tokens
+    }
+
+    /// Get the definition of the macro named *name*, split into its parameter
+    /// text and its replacement text.
+    ///
+    /// Returns None if *name* isn't defined as a macro. Because `\let` copies
+    /// a control sequence's meaning, a name that was `\let` to a macro is a
+    /// macro itself and is handled the same way.
+    pub fn macro_definition(&self, name: &str) -> Option<(Vec<Token>, Vec<Token>)> {
+        let entry = self.eqtb.decode(self.cs_pointer(name)?);
+
+        if entry.classify(&self.engine) != eqtb::EntryKind::Macro {
+            return None;
+        }
+
+        let end_match = self.engine.symbols.lookup("END_MATCH") as CommandCode;
+        let mut params = self.token_list(entry.value);
+        let split = params
+            .iter()
+            .position(|t| matches!(t, Token::Char { cmd, .. } if *cmd == end_match))?;
+        let body = params.split_off(split + 1);
+        params.pop();
+        Some((params, body))
+    }
+
+    /// Get what the macro named *name* would turn into, if it takes no
+    /// parameters.
+    ///
+    /// This expands the macro by a single level only: the replacement text is
+    /// returned as is, without expanding any macros that it contains. Returns
+    /// None if *name* isn't a macro or if it takes parameters.
+    pub fn expand_once(&self, name: &str) -> Option<Vec<Token>> {
+        match self.macro_definition(name)? {
+            (params, body) if params.is_empty() => Some(body),
+            _ => None,
+        }
+    }
+
+    /// Find the eqtb location of the control sequence named *name*, without
+    /// decoding its meaning.
+    ///
+    /// The name is given without the leading backslash. Single-character
+    /// names map to their `SINGLE_BASE` slots and the empty name to the null
+    /// control sequence, whether or not they're defined; other names return
+    /// None if they aren't in the hash. The result can be passed to
+    /// [`eqtb::EquivalenciesTable::decode`] or
+    /// [`eqtb::EquivalenciesTable::raw`].
+    pub fn cs_eqtb_index(&self, name: &str) -> Option<EqtbPointer> {
+        self.cs_pointer(name)
+    }
+
+    /// Find the eqtb location of the control sequence named *name*.
+    fn cs_pointer(&self, name: &str) -> Option<EqtbPointer> {
+        let mut chars = name.chars();
+
+        match (chars.next(), chars.next()) {
+            (None, _) => Some(self.engine.symbols.lookup("NULL_CS") as EqtbPointer),
+            (Some(c), None) => {
+                Some(self.engine.symbols.lookup("SINGLE_BASE") as EqtbPointer + c as EqtbPointer)
+            }
+            _ => self.cshash.lookup(name, &self.strings),
+        }
+    }
+
+    fn cseqs(&self) -> impl Iterator<Item = (String, EqtbPointer)> {
+        // This is lame; we shouldn't need to make a big buffer, but I'm too
+        // lazy to write real iterater implementation right now.
+
+        let null_cs = (
+            "".to_owned(),
+            self.engine.symbols.lookup("NULL_CS") as EqtbPointer,
+        );
+        let null_cs = std::iter::once(null_cs);
+
+        let single_base = self.engine.symbols.lookup("SINGLE_BASE");
+        let single_letters = valid_usvs().map(move |usv| {
+            (
+                char::from_u32(usv as u32).unwrap().to_string(),
+                single_base as i32 + usv,
+            )
+        });
+
+        let ml_data: Vec<(String, EqtbPointer)> = self
+            .strings
+            .iter()
+            .filter_map(|(_, name)| {
+                self.cshash
+                    .lookup(name, &self.strings)
+                    .map(|ptr| (name.to_owned(), ptr))
+            })
+            .collect();
+
+        null_cs.chain(single_letters).chain(ml_data)
+    }
+
+    /// Get the fraction of the control-sequence hash table's slots that are
+    /// in use.
+    ///
+    /// The denominator is every slot that can hold a new name: the main hash
+    /// region of `hash_size` slots, of which the first `hash_prime` start the
+    /// hash chains, plus the `hash_extra` slots. A format whose load factor
+    /// approaches 1 will run out of room for new definitions. See
+    /// [`cshash::ControlSeqHash::occupied_slots`] for the raw count.
+    pub fn hash_load_factor(&self) -> f64 {
+        self.cshash.occupied_slots() as f64 / self.cshash.slot_count() as f64
+    }
+
+    /// Get the number of strings in the format's string table.
+    pub fn strings_len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Get the number of fonts preloaded in the format, including the
+    /// `\nullfont`.
+    pub fn font_count(&self) -> usize {
+        self.fonts.fonts.len()
+    }
+
+    /// Get the number of control sequences that have definitions, including
+    /// the single-character and null control sequences.
+    pub fn defined_cs_count(&self) -> usize {
+        self.cseqs()
+            .filter(|(_, ptr)| {
+                self.eqtb.decode(*ptr).classify(&self.engine) != eqtb::EntryKind::Undefined
+            })
+            .count()
+    }
+
+    /// Get the number of words in the hyphenation exception dictionary, as
+    /// recorded in the format.
+    pub fn hyphenation_exception_count(&self) -> usize {
+        self.hyph_exceptions.count.max(0) as usize
+    }
+
+    /// Get the format's table of equivalents.
+    ///
+    /// Use [`Format::cs_eqtb_index`] to find the entry for a named control
+    /// sequence.
+    pub fn eqtb(&self) -> &eqtb::EquivalenciesTable {
+        &self.eqtb
+    }
+
+    /// Get the format's string table.
+    pub fn strings(&self) -> &stringtable::StringTable {
+        &self.strings
+    }
+
+    /// Iterate over the strings in the format's string table along with their
+    /// pointers.
+    pub fn strings_iter(&self) -> impl Iterator<Item = (stringtable::StrPointer, &str)> {
+        self.strings.iter()
+    }
+
+    /// Check whether the e-TeX extensions are enabled in this format.
+    ///
+    /// Classic e-TeX engines start in a TeX-compatibility mode, and only enable
+    /// the extensions if the format is built in "extended mode". Tectonic's
+    /// engine has no compatibility mode: the e-TeX extensions are always
+    /// active, and format files carry no flag to say otherwise. So this always
+    /// returns true. It's provided so that analyses written with other engines
+    /// in mind can ask the question explicitly.
+    pub fn etex_mode(&self) -> bool {
+        true
+    }
+
+    /// Get the job name that was in effect when this format was dumped.
+    ///
+    /// The engine doesn't save the job name, `\inputlineno`, or any other
+    /// state of the input files in formats, and the format identification
+    /// string that mentions the job name is removed from the string pool
+    /// before dumping. So this always returns None. It's provided so that
+    /// checks for leftover job state can be written explicitly.
+    pub fn job_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the value of `\globaldefs` saved in this format.
+    ///
+    /// If positive, all assignments are global; if negative, all are local,
+    /// even those prefixed with `\global`.
+    pub fn globaldefs(&self) -> i32 {
+        self.int_parameter_named("global_defs")
+    }
+
+    /// Get the fonts preloaded in this format, indexed by font number.
+    ///
+    /// Font number zero is always the `\nullfont`.
+    pub fn fonts(&self) -> &[fonts::Font] {
+        &self.fonts.fonts[..]
+    }
+
+    /// Iterate over the string pointers referenced by the preloaded fonts.
+    ///
+    /// For each font in turn, this yields the pointer to its name and then the
+    /// pointer to its area, so that all of the font-related strings can be
+    /// resolved in one pass.
+    pub fn font_string_refs(&self) -> impl Iterator<Item = stringtable::StrPointer> + '_ {
+        self.fonts
+            .fonts
+            .iter()
+            .flat_map(|font| std::iter::once(font.name).chain(std::iter::once(font.area)))
+    }
+
+    /// Get the identification string of a preloaded font, as `\fontname`
+    /// would produce it.
+    ///
+    /// As in TeX, this is the font's name, followed by its "at" size if that
+    /// differs from its design size, e.g. `cmr10 at 12.0pt`. TeX doesn't
+    /// include the font's area, and neither does this. XeTeX's richer
+    /// identifiers for native fonts, with quoted names and feature strings,
+    /// never arise here, because formats can't contain native fonts. Returns
+    /// `None` if *font* is not a valid font number.
+    pub fn font_identifier(&self, font: usize) -> Option<String> {
+        let f = self.fonts.fonts.get(font)?;
+        Some(fmt_font_identifier(
+            self.fmt_str_pointer(f.name),
+            f.size,
+            f.design_size,
+        ))
+    }
+
+    /// Get the number of the current font.
+    ///
+    /// For most formats, this is zero, the `\nullfont`.
+    pub fn current_font(&self) -> usize {
+        self.eqtb
+            .decode(self.engine.symbols.lookup("CUR_FONT_LOC") as EqtbPointer)
+            .value as usize
+    }
+
+    /// Get the number of the font assigned to a math family at a given size.
+    ///
+    /// This is the equivalent of `\textfont`, `\scriptfont`, or
+    /// `\scriptscriptfont`. The size is given as one of the engine's
+    /// [`MathFontSizes`], which double as the offsets of the three regions of
+    /// family fonts in the eqtb; there's no separate `TextSize` type. Returns
+    /// `None` if *family* is not a valid math family number.
+    pub fn math_family_font(&self, size: MathFontSizes, family: usize) -> Option<usize> {
+        let n_families = self.engine.symbols.lookup("NUMBER_MATH_FAMILIES") as usize;
+
+        if family >= n_families {
+            return None;
+        }
+
+        Some(
+            self.eqtb
+                .decode(
+                    self.engine.symbols.lookup("MATH_FONT_BASE") as EqtbPointer
+                        + size as EqtbPointer
+                        + family as EqtbPointer,
+                )
+                .value as usize,
+        )
+    }
+
+    /// Get the values of the integer parameters that control penalties, such
+    /// as `\interlinepenalty` and `\hyphenpenalty`.
+    ///
+    /// The map is keyed by the parameter names as used in the engine, e.g.
+    /// `inter_line_penalty`.
+    pub fn penalty_settings(&self) -> BTreeMap<&'static str, i32> {
+        self.engine
+            .int_pars
+            .iter()
+            .filter(|par| par.name().ends_with("_penalty"))
+            .map(|par| (par.name(), self.int_parameter(par)))
+            .collect()
+    }
+
+    /// Get the values of the XeTeX integer parameters that affect the output
+    /// of the engine, such as `\XeTeXgenerateactualtext`, which controls
+    /// whether PDF output gets "actual text" spans for copying and pasting.
+    ///
+    /// The map is keyed by the parameter names as used in the engine, e.g.
+    /// `XeTeX_generate_actual_text`, and only includes the parameters that
+    /// are defined by the engine version associated with this format.
+    pub fn xetex_output_flags(&self) -> BTreeMap<&'static str, i32> {
+        const FLAGS: &[&str] = &[
+            "XeTeX_generate_actual_text",
+            "XeTeX_protrude_chars",
+            "XeTeX_upwards",
+            "XeTeX_use_glyph_metrics",
+            "XeTeX_interword_space_shaping",
+            "texxet",
+        ];
+
+        self.engine
+            .int_pars
+            .iter()
+            .filter(|par| FLAGS.contains(&par.name()))
+            .map(|par| (par.name(), self.int_parameter(par)))
+            .collect()
+    }
+
+    /// Get the values of the dimension parameters, such as `\hsize` and
+    /// `\parindent`.
+    ///
+    /// The map is keyed by the parameter names as used in the engine, e.g.
+    /// `par_indent`.
+    pub fn dimen_parameters(&self) -> BTreeMap<&'static str, Fixed> {
+        self.engine
+            .dimen_pars
+            .iter()
+            .map(|par| (par.name(), self.dimen_parameter(par)))
+            .collect()
+    }
+
+    /// Get the value of a dimension parameter.
+    fn dimen_parameter(&self, par: &dimenpars::DimenPar) -> Fixed {
+        let loc = self.engine.symbols.lookup("DIMEN_BASE")
+            + self.engine.symbols.lookup(&par.symbol_name());
+        Fixed(self.eqtb.decode(loc as EqtbPointer).value)
+    }
+
+    /// Get the value of a dimension parameter, identified by its name in the
+    /// engine.
+    fn dimen_parameter_named(&self, name: &str) -> Fixed {
+        self.engine
+            .dimen_pars
+            .iter()
+            .find(|par| par.name() == name)
+            .map(|par| self.dimen_parameter(par))
+            .unwrap_or_default()
+    }
+
+    /// Get the value of a glue parameter, identified by its name in the
+    /// engine.
+    fn glue_parameter_named(&self, name: &str) -> mem::GlueSpec {
+        match self.engine.glue_pars.iter().find(|par| par.name() == name) {
+            Some(par) => {
+                let loc = self.engine.symbols.lookup("GLUE_BASE")
+                    + self.engine.symbols.lookup(&par.symbol_name());
+                self.mem
+                    .decode_glue(self.eqtb.decode(loc as EqtbPointer).value)
+            }
+
+            None => mem::GlueSpec::default(),
+        }
+    }
+
+    /// Get the current `\parshape`, as the indentation and length of each
+    /// line. The result is empty if no shape is set.
+    pub fn par_shape(&self) -> Vec<(Fixed, Fixed)> {
+        match self.eqtb_local("LOCAL__par_shape").value {
+            TEX_NULL => Vec::new(),
+            p => self.mem.decode_par_shape(p),
+        }
+    }
+
+    /// Get the parameters that control the shape of paragraphs.
+    pub fn paragraph_shaping(&self) -> settings::ParagraphShaping {
+        settings::ParagraphShaping {
+            par_shape: self.par_shape(),
+            hang_indent: self.dimen_parameter_named("hang_indent"),
+            hang_after: self.int_parameter_named("hang_after"),
+        }
+    }
+
+    /// Get the name of the primitive with the given command code and
+    /// argument, if the format defines one.
+    ///
+    /// This is the inverse of looking up a primitive by name, and is what's
+    /// needed to describe a control sequence that has been `\let` to a
+    /// primitive. If several primitives share the code, the canonical one is
+    /// returned, as with [`Engine::primitive_name`]. The name is returned
+    /// without a leading backslash.
+    pub fn primitive_name(&self, cmd: CommandCode, chr: CommandArgument) -> Option<&str> {
+        self.primitives
+            .get(&(cmd, chr))
+            .and_then(|names| names.first())
+            .map(|s| s.as_ref())
+    }
+
+    /// Iterate over the names of all of the primitives defined in this format.
+    fn primitive_names(&self) -> impl Iterator<Item = &str> {
+        self.primitives.values().flatten().map(|s| s.as_ref())
+    }
+
+    /// List the pdfTeX-compatibility primitives, like `\strcmp` and
+    /// `\shellescape`, that this format defines.
+    ///
+    /// The names are the ones that XeTeX uses, which usually lack pdfTeX's
+    /// `pdf` prefix, and are returned without a leading backslash.
+    pub fn pdftex_compat_primitives(&self) -> Vec<&str> {
+        let defined: BTreeSet<&str> = self.primitive_names().collect();
+
+        PDFTEX_COMPAT_PRIMITIVES
+            .iter()
+            .copied()
+            .filter(|name| defined.contains(name))
+            .collect()
+    }
+
+    /// List XeTeX's graphics-inclusion primitives, like `\XeTeXpicfile` and
+    /// `\XeTeXpdffile`, that this format defines.
+    ///
+    /// If this is empty, documents using the format can't include images.
+    /// The names are returned without a leading backslash.
+    pub fn graphics_primitives(&self) -> Vec<&str> {
+        let defined: BTreeSet<&str> = self.primitive_names().collect();
+
+        GRAPHICS_PRIMITIVES
+            .iter()
+            .copied()
+            .filter(|name| defined.contains(name))
+            .collect()
+    }
+
+    /// Compute a fingerprint of the set of primitives defined in this format.
+    ///
+    /// Formats built by the same generation of the engine share the same
+    /// primitives, regardless of which macro packages they load, so this can
+    /// be used to group formats by engine. The signature is the 64-bit FNV-1a
+    /// hash of the distinct primitive names, sorted by their UTF-8 bytes, with
+    /// each name followed by a zero byte. It depends only on the names, not on
+    /// their command codes.
+    pub fn primitive_set_signature(&self) -> u64 {
+        primitive_set_signature(self.primitive_names())
+    }
+
+    /// Get the `\Umathcode` of the character *c*.
+    pub fn u_math_code(&self, c: i32) -> UMathCode {
+        let base = self.engine.symbols.lookup("MATH_CODE_BASE") as EqtbPointer;
+        self.eqtb.decode(base + c).value.into()
+    }
+
+    /// Get all of the `\Umathcode`s that differ from their initial values,
+    /// keyed by character.
+    pub fn u_math_codes(&self) -> BTreeMap<i32, UMathCode> {
+        valid_usvs()
+            .map(|c| (c, self.u_math_code(c)))
+            .filter(|(c, code)| *code != UMathCode::default_for(*c))
+            .collect()
+    }
+
+    /// Get the `\sfcode` of the character *c*.
+    pub fn sf_code(&self, c: i32) -> i32 {
+        let base = self.engine.symbols.lookup("SF_CODE_BASE") as EqtbPointer;
+        self.eqtb.decode(base + c).value
+    }
+
+    /// Get all of the `\sfcode`s that differ from their initial values,
+    /// keyed by character.
+    ///
+    /// The uppercase Latin letters start out with `\sfcode`s of 999, not
+    /// 1000, so they're only included if they've been changed from that.
+    pub fn sf_codes(&self) -> BTreeMap<i32, i32> {
+        valid_usvs()
+            .map(|c| (c, self.sf_code(c)))
+            .filter(|(c, code)| *code != codes::initial_sf_code(*c))
+            .collect()
+    }
+
+    /// Dump the `\sfcode`s that differ from their initial values.
+    pub fn dump_sfcodes<W: Write>(&self, stream: &mut W) -> Result<()> {
+        for (c, code) in self.sf_codes() {
+            writeln!(stream, "{} => {}", fmt_usv(c), code)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the settings that control the spacing of inline math.
+    pub fn math_spacing(&self) -> settings::MathSpacing {
+        settings::MathSpacing {
+            math_surround: self.dimen_parameter_named("math_surround"),
+            thin_mu_skip: self.glue_parameter_named("thin_mu_skip"),
+            med_mu_skip: self.glue_parameter_named("med_mu_skip"),
+            thick_mu_skip: self.glue_parameter_named("thick_mu_skip"),
+        }
+    }
+
+    /// Get the settings that control the sizes of math delimiters.
+    pub fn delimiter_settings(&self) -> settings::DelimiterSettings {
+        settings::DelimiterSettings {
+            delimiter_factor: self.int_parameter_named("delimiter_factor"),
+            delimiter_shortfall: self.dimen_parameter_named("delimiter_shortfall"),
+            null_delimiter_space: self.dimen_parameter_named("null_delimiter_space"),
+        }
+    }
+
+    /// Get the settings that control when overfull boxes are reported.
+    pub fn box_tolerance_settings(&self) -> settings::BoxToleranceSettings {
+        settings::BoxToleranceSettings {
+            hfuzz: self.dimen_parameter_named("hfuzz"),
+            vfuzz: self.dimen_parameter_named("vfuzz"),
+            overfull_rule: self.dimen_parameter_named("overfull_rule"),
+        }
+    }
+
+    /// Get the glue parameters that govern how paragraphs are filled and
+    /// justified.
+    pub fn paragraph_glue_settings(&self) -> settings::ParagraphGlueSettings {
+        settings::ParagraphGlueSettings {
+            par_skip: self.glue_parameter_named("par_skip"),
+            left_skip: self.glue_parameter_named("left_skip"),
+            right_skip: self.glue_parameter_named("right_skip"),
+            space_skip: self.glue_parameter_named("space_skip"),
+            par_fill_skip: self.glue_parameter_named("par_fill_skip"),
+        }
+    }
+
+    /// Get the parameters that govern the spacing between lines.
+    pub fn baseline_settings(&self) -> settings::BaselineSettings {
+        settings::BaselineSettings {
+            baseline_skip: self.glue_parameter_named("baseline_skip"),
+            line_skip: self.glue_parameter_named("line_skip"),
+            line_skip_limit: self.dimen_parameter_named("line_skip_limit"),
+        }
+    }
+
+    /// Get the parameters that relax line breaking for difficult paragraphs.
+    pub fn linebreak_relaxation(&self) -> settings::LinebreakRelaxation {
+        settings::LinebreakRelaxation {
+            emergency_stretch: self.dimen_parameter_named("emergency_stretch"),
+            looseness: self.int_parameter_named("looseness"),
+        }
+    }
+
+    /// Get the date and time at which the format was built.
+    ///
+    /// This returns None if `\year`, `\month`, and `\day` aren't all
+    /// positive, as when the engine that built the format didn't set them.
+    pub fn build_time(&self) -> Option<settings::BuildTime> {
+        let t = settings::BuildTime {
+            year: self.int_parameter_named("year"),
+            month: self.int_parameter_named("month"),
+            day: self.int_parameter_named("day"),
+            time: self.int_parameter_named("time"),
+        };
+
+        if t.year > 0 && t.month > 0 && t.day > 0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// Get the configuration of the output routine.
+    pub fn output_routine(&self) -> settings::OutputRoutineInfo {
+        settings::OutputRoutineInfo {
+            output: self.token_list(self.eqtb_local("LOCAL__output_routine").value),
+            max_dead_cycles: self.int_parameter_named("max_dead_cycles"),
+        }
+    }
+
+    /// Report whether the lists inspected by `\lastbox`, `\lastkern`, and
+    /// related commands are empty, as they should be in any format.
+    pub fn last_node_state(&self) -> mem::LastNodeState {
+        self.mem.last_node_state()
+    }
+
+    /// Get the settings that control the verbosity of TeX's diagnostics.
+    pub fn diagnostics_settings(&self) -> settings::DiagnosticsSettings {
+        settings::DiagnosticsSettings {
+            show_box_breadth: self.int_parameter_named("show_box_breadth"),
+            show_box_depth: self.int_parameter_named("show_box_depth"),
+            error_context_lines: self.int_parameter_named("error_context_lines"),
+            hbadness: self.int_parameter_named("hbadness"),
+            vbadness: self.int_parameter_named("vbadness"),
+        }
+    }
+
+    /// Get XeTeX's settings for reading input files, which determine how
+    /// the format expects input bytes to be interpreted.
+    ///
+    /// These parameters are present in every format version supported by this
+    /// crate.
+    pub fn xetex_input_settings(&self) -> input::InputSettings {
+        input::InputSettings {
+            default_encoding: input::InputEncoding::decode(
+                self.int_parameter_named("XeTeX_default_input_mode"),
+                self.int_parameter_named("XeTeX_default_input_encoding"),
+                &self.strings,
+            ),
+            normalization: self.int_parameter_named("XeTeX_input_normalization").into(),
+        }
+    }
+
+    /// Get the Unicode normalization that the format applies to input, as
+    /// set by `\XeTeXinputnormalization`.
+    ///
+    /// This is the same as the `normalization` field of
+    /// [`Format::xetex_input_settings`]. Every format handled by this crate
+    /// is a XeTeX format, so the setting is always present; it is
+    /// [`input::InputNormalization::None`] unless the format changed it.
+    pub fn unicode_normalization(&self) -> input::InputNormalization {
+        self.int_parameter_named("XeTeX_input_normalization").into()
+    }
+
+    /// Get XeTeX's settings for breaking lines in scripts that don't use
+    /// spaces between words, such as Chinese and Thai.
+    pub fn xetex_linebreak_settings(&self) -> settings::LinebreakSettings {
+        let locale = match self.int_parameter_named("xetex_linebreak_locale") {
+            0 => None,
+            sp => Some(self.fmt_str_pointer(sp)),
+        };
+
+        settings::LinebreakSettings {
+            locale,
+            skip: self.glue_parameter_named("XeTeX_linebreak_skip"),
+            penalty: self.int_parameter_named("XeTeX_linebreak_penalty"),
+        }
+    }
+
+    /// Get the settings that control the handling of line endings.
+    pub fn line_ending_analysis(&self) -> settings::LineEndingAnalysis {
+        let catcode = |c: i32| {
+            if (0..MAX_USV).contains(&c) {
+                self.eqtb_catcode(c).ok()
+            } else {
+                None
+            }
+        };
+
+        let end_line_char = self.int_parameter_named("end_line_char");
+        let new_line_char = self.int_parameter_named("new_line_char");
+
+        settings::LineEndingAnalysis {
+            end_line_char,
+            end_line_catcode: catcode(end_line_char),
+            new_line_char,
+            new_line_catcode: catcode(new_line_char),
+        }
+    }
+
+    /// Get the settings that control the direction of typesetting.
+    pub fn xetex_direction_settings(&self) -> settings::DirectionSettings {
+        settings::DirectionSettings {
+            upwards_mode: self.int_parameter_named("XeTeX_upwards"),
+            texxet_state: self.int_parameter_named("texxet"),
+            pre_display_direction: self.int_parameter_named("pre_display_direction"),
+        }
+    }
+
+    /// Get XeTeX's integer settings related to line breaking.
+    pub fn xetex_break_settings(&self) -> settings::XetexBreakSettings {
+        settings::XetexBreakSettings {
+            dash_break_state: self.int_parameter_named("xetex_dash_break"),
+            hyphenatable_length: self.int_parameter_named("XeTeX_hyphenatable_length"),
+            tracing_fonts: self.int_parameter_named("XeTeX_tracing_fonts"),
+        }
+    }
+
+    /// Get the value of an integer parameter.
+    fn int_parameter(&self, par: &intpars::IntPar) -> i32 {
+        let loc =
+            self.engine.symbols.lookup("INT_BASE") + self.engine.symbols.lookup(&par.symbol_name());
+        self.eqtb.decode(loc as EqtbPointer).value
+    }
+
+    /// Get the value of an integer parameter, identified by its name in the
+    /// engine.
+    fn int_parameter_named(&self, name: &str) -> i32 {
+        self.engine
+            .int_pars
+            .iter()
+            .find(|par| par.name() == name)
+            .map(|par| self.int_parameter(par))
+            .unwrap_or(0)
+    }
+
+    /// Get the values of the `\count` registers whose numbers lie in *range*.
+    ///
+    /// The results are ordered by register number and include registers that
+    /// have never been set, with their default values. Register numbers
+    /// beyond the engine maximum are ignored. The registers are decoded
+    /// lazily as the iterator advances, so that very large ranges can be
+    /// processed without building up a big intermediate collection.
+    pub fn count_registers_in(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, i32)> + '_ {
+        self.registers_in(range, "COUNT_BASE", "INT_VAL", 0)
+    }
+
+    /// Get the values of the `\dimen` registers whose numbers lie in *range*.
+    ///
+    /// See [`Format::count_registers_in`] for details.
+    pub fn dimen_registers_in(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, Fixed)> + '_ {
+        self.registers_in(range, "SCALED_BASE", "DIMEN_VAL", 0)
+            .map(|(n, v)| (n, Fixed(v)))
+    }
 
-            let cur_cat = self.eqtb_catcode(chr)?;
-            let cmd_desc = self.engine.commands.describe(entry.ty, entry.value);
+    /// Get the values of the `\skip` registers whose numbers lie in *range*.
+    ///
+    /// See [`Format::count_registers_in`] for details.
+    pub fn skip_registers_in(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, mem::GlueSpec)> + '_ {
+        // The default, `zero_glue`, lives at `mem_bot` = 0.
+        self.registers_in(range, "SKIP_BASE", "GLUE_VAL", 0)
+            .map(move |(n, p)| (n, self.mem.decode_glue(p)))
+    }
 
-            writeln!(
-                stream,
-                "{} ({}) => {}",
-                fmt_usv(chr),
-                cur_cat.abbrev(),
-                cmd_desc
-            )?;
-        }
+    /// Get the values of the `\toks` registers whose numbers lie in *range*.
+    ///
+    /// See [`Format::count_registers_in`] for details.
+    pub fn toks_registers_in(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, Vec<Token>)> + '_ {
+        self.registers_in(range, "TOKS_BASE", "TOK_VAL", TEX_NULL)
+            .map(move |(n, p)| (n, self.token_list(p)))
+    }
 
-        Ok(())
+    /// Get the raw values of a set of registers: the eqtb values for the
+    /// low-numbered registers, and the sparse-array values for the rest.
+    fn registers_in(
+        &self,
+        range: Range<usize>,
+        base: &str,
+        sa_type: &str,
+        default: i32,
+    ) -> impl Iterator<Item = (usize, i32)> + '_ {
+        let base = self.engine.symbols.lookup(base) as EqtbPointer;
+        let sa_type = self.engine.symbols.lookup(sa_type) as usize;
+        let dimen_val = self.engine.symbols.lookup("DIMEN_VAL") as usize;
+        let end = range.end.min(MAX_REG_NUM + 1);
+
+        (range.start..end).map(move |n| {
+            let value = if n < NUMBER_REGS {
+                self.eqtb.decode(base + n as EqtbPointer).value
+            } else {
+                match self.mem.find_sa_element(sa_type, n as i32) {
+                    None => default,
+                    Some(p) if sa_type <= dimen_val => self.mem.sa_int(p),
+                    Some(p) => self.mem.sa_ptr(p),
+                }
+            };
+
+            (n, value)
+        })
     }
 
-    pub fn dump_catcodes<W: Write>(&self, stream: &mut W) -> Result<()> {
-        let mut blocks = vec![Vec::new(); 16];
-        let mut start = 0;
-        let mut prev = start;
-        let mut cur_cat = self.eqtb_catcode(start)?;
+    /// Normalize a word the way TeX does when it enters a `\hyphenation`
+    /// exception in language *lang*.
+    ///
+    /// Each character is mapped through the format's `\lccode` table, or
+    /// through the language's hyphenation codes if they were saved with
+    /// `\savinghyphcodes`, so the result can be compared to the words stored
+    /// in the exception dictionary. Characters with a code of zero aren't
+    /// letters as far as TeX is concerned; TeX ignores them with a "Not a
+    /// letter" error, and so they're dropped here too.
+    pub fn normalize_hyphenation_word(&self, lang: usize, word: &str) -> String {
+        let hyph_index = self.hyph_index(norm_lang(lang));
+
+        word.chars()
+            .filter_map(|c| match self.hyph_lc_code(hyph_index, c as i32) {
+                0 => None,
+                lc => char::from_u32(lc as u32),
+            })
+            .collect()
+    }
 
-        for chr in valid_usvs().skip(1) {
-            let cat = self.eqtb_catcode(chr)?;
+    /// Hyphenate a word the way TeX would, in language *lang*.
+    ///
+    /// As in TeX, the exception dictionary is consulted first, and the
+    /// patterns are only used if the word isn't found there. The hyphens are
+    /// then limited by the minima given by [`Format::hyphenation_min`].
+    /// Each element of the result is a place where a hyphen is allowed, given
+    /// as the number of characters of *word* that precede it. A word containing
+    /// non-letters has no hyphens.
+    pub fn full_hyphenation(&self, lang: usize, word: &str) -> Vec<usize> {
+        let lang = norm_lang(lang);
+        let (hc, ends) = match self.hyphenation_codes(lang, word) {
+            Some(t) => t,
+            None => return Vec::new(),
+        };
 
-            if cat != cur_cat {
-                blocks[cur_cat as usize].push((start, prev));
-                start = chr;
-                cur_cat = cat;
-            }
+        let (l_hyf, r_hyf) = match self.hyphenation_min(lang) {
+            Some((l, r)) => (l as usize, r as usize),
+            None => return Vec::new(),
+        };
+        let hn = hc.len();
 
-            prev = chr;
+        if hn < l_hyf + r_hyf {
+            return Vec::new();
         }
 
-        blocks[cur_cat as usize].push((start, prev));
+        let mut hyf = self
+            .exception_values(lang, &hc)
+            .unwrap_or_else(|| self.trie.hyphenation_values(lang, &hc));
 
-        for cat in 0..16 {
-            if cat > 0 {
-                writeln!(stream)?;
+        for (j, h) in hyf.iter_mut().enumerate() {
+            if j < l_hyf || j > hn - r_hyf {
+                *h = 0;
             }
+        }
 
-            writeln!(stream, "{}:", CatCode::from_i32(cat).unwrap().description())?;
+        hyf_positions(&hyf, &ends)
+    }
 
-            for block in &blocks[cat as usize] {
-                let (start, end) = *block;
+    /// Get the minimum number of characters that must precede and follow a
+    /// hyphen in language *lang*, or None if *lang* isn't a valid language
+    /// number.
+    ///
+    /// TeX doesn't save these minima per language. It records the values of
+    /// `\lefthyphenmin` and `\righthyphenmin` in the paragraph being typeset
+    /// whenever the language changes, but the format only holds the global
+    /// parameters, so those are what this returns for every language. The
+    /// values are normalized as the engine does, into the range 1 to 63.
+    pub fn hyphenation_min(&self, lang: usize) -> Option<(u8, u8)> {
+        if lang > hyphenation::BIGGEST_LANG {
+            return None;
+        }
 
-                if end == start {
-                    writeln!(stream, "    {}", fmt_usv(start))?;
+        Some((
+            norm_min(self.int_parameter_named("left_hyphen_min")) as u8,
+            norm_min(self.int_parameter_named("right_hyphen_min")) as u8,
+        ))
+    }
+
+    /// Check that words are hyphenated as expected in language *lang*.
+    ///
+    /// Each case gives a word and its expected hyphen positions, in the form
+    /// returned by [`Format::full_hyphenation`], which is used to hyphenate
+    /// it. The cases that don't match are returned, in order, so an empty
+    /// result means that every word came out right. This is handy for
+    /// checking that a rebuilt format still hyphenates a known vocabulary
+    /// correctly.
+    pub fn check_hyphenation(
+        &self,
+        lang: usize,
+        cases: &[(&str, &[usize])],
+    ) -> Vec<hyphenation::HyphenationMismatch> {
+        cases
+            .iter()
+            .filter_map(|(word, expected)| {
+                let actual = self.full_hyphenation(lang, word);
+
+                if actual[..] == expected[..] {
+                    None
                 } else {
-                    writeln!(stream, "    {} - {}", fmt_usv(start), fmt_usv(end))?;
+                    Some(hyphenation::HyphenationMismatch {
+                        word: (*word).to_owned(),
+                        expected: expected.to_vec(),
+                        actual,
+                    })
                 }
-            }
+            })
+            .collect()
+    }
+
+    /// Hyphenate a word using the patterns of language *lang*.
+    ///
+    /// Unlike [`Format::full_hyphenation`], this ignores the exception
+    /// dictionary and `\lefthyphenmin`/`\righthyphenmin`, which is useful for
+    /// checking the patterns themselves.
+    pub fn hyphenate(&self, lang: usize, word: &str) -> Vec<usize> {
+        let lang = norm_lang(lang);
+
+        match self.hyphenation_codes(lang, word) {
+            Some((hc, ends)) => hyf_positions(&self.trie.hyphenation_values(lang, &hc), &ends),
+            None => Vec::new(),
         }
+    }
 
-        Ok(())
+    /// Summarize the hyphenation patterns of language *lang*.
+    ///
+    /// This is a quick way to check that a language's patterns were loaded
+    /// as expected without examining them individually. See
+    /// [`hyphenation::PatternStats`] for the details.
+    pub fn pattern_coverage(&self, lang: usize) -> hyphenation::PatternStats {
+        self.trie.pattern_stats(norm_lang(lang))
     }
 
-    pub fn dump_cseqs<W: Write>(&self, stream: &mut W, extended: bool) -> Result<()> {
-        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+    /// Look up a word in the hyphenation exception dictionary of language
+    /// *lang*, returning its hyphen positions if it's present.
+    ///
+    /// The positions are reported as in [`Format::full_hyphenation`], but
+    /// without applying `\lefthyphenmin`/`\righthyphenmin`.
+    pub fn hyphenation_exception(&self, lang: usize, word: &str) -> Option<Vec<usize>> {
+        let lang = norm_lang(lang);
+        let (hc, ends) = self.hyphenation_codes(lang, word)?;
+        let hyf = self.exception_values(lang, &hc)?;
+        Some(hyf_positions(&hyf, &ends))
+    }
 
-        for (name, ptr) in self.cseqs() {
-            let entry = self.eqtb.decode(ptr);
+    /// List the languages that have hyphenation exceptions, in increasing
+    /// order.
+    ///
+    /// This is independent of the hyphenation patterns: a format might have
+    /// patterns for one set of languages and exceptions for another.
+    pub fn languages_with_exceptions(&self) -> Vec<usize> {
+        // The last character of each word is its language.
+        let langs: BTreeSet<usize> = (0..hyphenation::HYPH_SIZE)
+            .filter_map(|h| self.hyph_exceptions.word_text(h, &self.strings))
+            .filter_map(|text| text.chars().last())
+            .map(|c| c as usize)
+            .collect();
+        langs.into_iter().collect()
+    }
 
-            if entry.ty == undefined_cs_cmd {
-                continue;
-            }
+    /// Get the number of occupied slots in the hyphenation exception table.
+    ///
+    /// Each exception word occupies one slot, and the engine gives up with an
+    /// overflow error when all of them are used; compare this value to
+    /// [`Format::hyphenation_slots_total`].
+    pub fn hyphenation_slots_used(&self) -> usize {
+        self.hyph_exceptions.slots_used()
+    }
 
-            let cs_desc = fmt_csname(name);
+    /// Get the total number of slots in the hyphenation exception table.
+    pub fn hyphenation_slots_total(&self) -> usize {
+        hyphenation::HYPH_SIZE
+    }
 
-            let (cmd_desc, extended) = if extended {
-                self.engine
-                    .commands
-                    .describe_extended(entry.ty, entry.value, self)
-            } else {
-                (self.engine.commands.describe(entry.ty, entry.value), None)
-            };
+    /// Get the hyphenation values for a word from the exception dictionary,
+    /// in the form returned by [`hyphenation::HyphenationTrie::hyphenation_values`].
+    fn exception_values(&self, lang: usize, hc: &[u16]) -> Option<Vec<i16>> {
+        let mut key = hc.to_vec();
+        key.push(lang as u16);
 
-            writeln!(stream, "{} => {}", cs_desc, cmd_desc)?;
+        let mut p = self.hyph_exceptions.lookup(&key, &self.strings)?;
+        let mut hyf = vec![0i16; hc.len() + 1];
+        let mut n = 0;
 
-            if let Some(e) = extended {
-                writeln!(stream, "--------\n{}\n--------", e)?;
+        // Bound the walk in case the list is corrupt.
+        while p != TEX_NULL && n <= hc.len() {
+            let (pos, next) = self.mem.decode_toklist(p);
+
+            if let Some(h) = usize::try_from(pos).ok().and_then(|i| hyf.get_mut(i)) {
+                *h = 1;
             }
+
+            p = next;
+            n += 1;
         }
 
-        Ok(())
+        Some(hyf)
     }
 
-    fn cseqs(&self) -> impl Iterator<Item = (String, EqtbPointer)> {
-        // This is lame; we shouldn't need to make a big buffer, but I'm too
-        // lazy to write real iterater implementation right now.
+    /// Get the UTF-16 hyphenation codes of a word in language *lang*, as TeX's
+    /// line breaker would compute them, along with the number of codes
+    /// through the end of each character of the word.
+    ///
+    /// Returns None if the word contains a non-letter.
+    fn hyphenation_codes(&self, lang: usize, word: &str) -> Option<(Vec<u16>, Vec<usize>)> {
+        let hyph_index = self.hyph_index(lang);
+        let mut hc = Vec::new();
+        let mut ends = Vec::new();
+
+        for c in word.chars() {
+            let lc = char::from_u32(self.hyph_lc_code(hyph_index, c as i32) as u32)?;
+
+            if lc == '\0' {
+                return None;
+            }
 
-        let null_cs = (
-            "".to_owned(),
-            self.engine.symbols.lookup("NULL_CS") as EqtbPointer,
-        );
-        let null_cs = std::iter::once(null_cs);
+            let mut buf = [0; 2];
+            hc.extend_from_slice(lc.encode_utf16(&mut buf));
+            ends.push(hc.len());
+        }
 
-        let single_base = self.engine.symbols.lookup("SINGLE_BASE");
-        let single_letters = valid_usvs().map(move |usv| {
-            (
-                char::from_u32(usv as u32).unwrap().to_string(),
-                single_base as i32 + usv,
-            )
-        });
+        Some((hc, ends))
+    }
 
-        let ml_data: Vec<(String, EqtbPointer)> = self
-            .strings
-            .all_sps()
-            .filter_map(|sp| {
-                let name = self.strings.lookup(sp).to_owned();
-                self.cshash
-                    .lookup(&name, &self.strings)
-                    .map(|ptr| (name, ptr))
-            })
-            .collect();
+    /// Find the hyphenation codes saved for language *lang* by
+    /// `\savinghyphcodes`, as for [`Format::hyph_lc_code`].
+    ///
+    /// This mirrors `set_hyph_index` in the engine, returning zero if the
+    /// language has no saved codes.
+    fn hyph_index(&self, lang: usize) -> i32 {
+        let hyph_start = self.trie.hyph_start as usize;
+
+        match (
+            self.trie.trc.get(hyph_start + lang),
+            self.trie.trl.get(hyph_start + lang),
+        ) {
+            (Some(&c), Some(&l)) if c as usize == lang => l,
+            _ => 0,
+        }
+    }
 
-        null_cs.chain(single_letters).chain(ml_data)
+    /// Get the "lowercase" code of a character for the purposes of
+    /// hyphenation.
+    ///
+    /// If *hyph_index* is nonzero, it points to a set of hyphenation codes
+    /// saved in the pattern trie by `\savinghyphcodes`, which take the place of
+    /// the `\lccode`s for the first 256 characters.
+    fn hyph_lc_code(&self, hyph_index: i32, c: i32) -> i32 {
+        if hyph_index == 0 || c > 255 {
+            self.eqtb
+                .decode(self.engine.symbols.lookup("LC_CODE_BASE") as EqtbPointer + c)
+                .value
+        } else {
+            let i = (hyph_index + c) as usize;
+
+            match (self.trie.trc.get(i), self.trie.tro.get(i)) {
+                (Some(&trc), Some(&tro)) if trc as i32 == c => tro,
+                _ => 0,
+            }
+        }
     }
 
     // Various stringifications that depend on the format data
@@ -269,9 +2707,23 @@ impl Format {
         result
     }
 
+    /// Format a string pointer, which may refer to a single character.
+    fn fmt_str_pointer(&self, sp: stringtable::StrPointer) -> String {
+        if sp >= TOO_BIG_CHAR && ((sp - TOO_BIG_CHAR) as usize) < self.strings.len() {
+            self.strings.lookup(sp).to_owned()
+        } else {
+            char::from_u32(sp as u32).map_or_else(String::new, |c| c.to_string())
+        }
+    }
+
+    /// Format a control-sequence name using this format's `\escapechar`.
+    fn fmt_csname(&self, name: &str) -> String {
+        fmt_csname_with_escape(name, self.int_parameter_named("escape_char"))
+    }
+
     fn fmt_cs_pointer(&self, ptr: EqtbPointer) -> String {
         if let Some(text) = self.cshash.stringify(ptr, &self.strings) {
-            fmt_csname(&text)
+            self.fmt_csname(&text)
         } else {
             format!("[undecodable cseq pointer {}]", ptr)
         }
@@ -287,6 +2739,13 @@ impl Format {
             .decode(self.engine.symbols.lookup("ACTIVE_BASE") as EqtbPointer + c)
     }
 
+    fn eqtb_local(&self, symbol: &str) -> eqtb::EqtbEntry {
+        self.eqtb.decode(
+            self.engine.symbols.lookup("LOCAL_BASE") as EqtbPointer
+                + self.engine.symbols.lookup(symbol) as EqtbPointer,
+        )
+    }
+
     fn eqtb_catcode(&self, c: i32) -> Result<CatCode> {
         assert!((0..MAX_USV).contains(&c));
         CatCode::from_i32(
@@ -297,181 +2756,442 @@ impl Format {
     }
 }
 
-fn parse_header(input: &[u8]) -> IResult<&[u8], i32> {
+/// Parses the data with [`Format::parse`].
+impl TryFrom<&[u8]> for Format {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        Format::parse(input)
+    }
+}
+
+/// Parses the data with [`Format::parse`]. The buffer is dropped afterwards,
+/// since the decoded format doesn't borrow from it.
+impl TryFrom<Vec<u8>> for Format {
+    type Error = Error;
+
+    fn try_from(input: Vec<u8>) -> Result<Self> {
+        Format::parse(&input[..])
+    }
+}
+
+/// Check that the input is at least long enough to hold a format header, to
+/// give a clear error for empty or badly truncated inputs.
+fn check_input_length(input: &[u8]) -> Result<()> {
+    ensure!(
+        input.len() >= MIN_HEADER_SIZE,
+        "input too short to be a format file ({} bytes)",
+        input.len()
+    );
+    Ok(())
+}
+
+fn parse_header(input: &[u8]) -> ParseResult<'_, i32> {
     let (input, _) = parseutils::satisfy_be_i32(HEADER_MAGIC)(input)?;
     be_i32(input)
 }
 
-fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
+/// Convert a nom result into our standard result type, describing any error
+/// relative to the *full* input.
+fn describe_nom_result<'a, T>(full: &[u8], result: ParseResult<'a, T>) -> Result<(&'a [u8], T)> {
+    match result {
+        Ok(t) => Ok(t),
+        Err(NomErr::Error(inner)) => bail!("parse error: {}", inner.describe(full)),
+        Err(NomErr::Failure(inner)) => bail!("parse failure: {}", inner.describe(full)),
+        Err(NomErr::Incomplete(_)) => bail!("incomplete input"),
+    }
+}
+
+/// A reusable format-file parser.
+///
+/// Parsing a format allocates several large buffers, and setting up the
+/// [`Engine`] description for its version takes some work too. A
+/// `FormatParser` can hold on to these things between parses: hand a
+/// [`Format`] that's no longer needed back to [`FormatParser::recycle`], and
+/// the next call to [`FormatParser::parse_into`] will reuse its storage. This
+/// helps when parsing many formats in a row.
+///
+/// ```no_run
+/// use tectonic_xetex_format::format::FormatParser;
+///
+/// # fn main() -> tectonic_errors::Result<()> {
+/// # let inputs: Vec<Vec<u8>> = Vec::new();
+/// let mut parser = FormatParser::default();
+///
+/// for data in &inputs {
+///     let format = parser.parse_into(&data[..])?;
+///     println!("{} strings", format.strings().len());
+///     parser.recycle(format);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct FormatParser {
+    tolerant: bool,
+    engine: Option<Engine>,
+    mem: Vec<u8>,
+    eqtb: Vec<u8>,
+    cshash: Vec<u8>,
+}
+
+impl FormatParser {
+    /// Set whether to tolerate a missing or corrupt footer.
+    ///
+    /// If enabled, a file that is truncated or damaged after its last section
+    /// is still parsed successfully, and [`Format::footer_ok`] returns false.
+    pub fn set_tolerant(&mut self, tolerant: bool) -> &mut Self {
+        self.tolerant = tolerant;
+        self
+    }
+
+    /// Parse a format file, reusing any storage recycled from earlier parses.
+    pub fn parse_into(&mut self, input: &[u8]) -> Result<Format> {
+        self.parse_with_progress(input, |_| {})
+    }
+
+    /// Parse a format file, reusing storage and reporting progress as in
+    /// [`Format::parse_with_progress`].
+    pub fn parse_with_progress<F: FnMut(ParseStage)>(
+        &mut self,
+        input: &[u8],
+        mut progress: F,
+    ) -> Result<Format> {
+        self.parse_reporting(input, &mut |stage, _| progress(stage))
+    }
+
+    /// Parse a format file, reusing storage and gathering statistics as in
+    /// [`Format::parse_with_stats`].
+    pub fn parse_with_stats(&mut self, input: &[u8]) -> Result<(Format, ParseStats)> {
+        let mut remaining = Vec::new();
+        let format = self.parse_reporting(input, &mut |stage, n| remaining.push((stage, n)))?;
+
+        let total_bytes = input.len() - format.trailing_bytes;
+        let mut start = input.len() - MIN_HEADER_SIZE;
+        let mut section_bytes = Vec::new();
+
+        for (stage, n) in remaining {
+            section_bytes.push((stage, start - n));
+            start = n;
+        }
+
+        let stats = ParseStats {
+            total_bytes,
+            header_bytes: MIN_HEADER_SIZE,
+            section_bytes,
+            footer_bytes: start - format.trailing_bytes,
+            strings: format.strings_len(),
+            fonts: format.font_count(),
+            control_sequences: format.defined_cs_count(),
+            hyphenation_exceptions: format.hyphenation_exception_count(),
+            trie_entries: format.trie.max as usize + 1,
+            trie_ops: format.trie.op_ptr as usize,
+        };
+
+        Ok((format, stats))
+    }
+
+    /// Parse a format file, invoking *progress* with each stage along with
+    /// the number of input bytes remaining after it.
+    fn parse_reporting(
+        &mut self,
+        input: &[u8],
+        progress: &mut dyn FnMut(ParseStage, usize),
+    ) -> Result<Format> {
+        let full = input;
+        check_input_length(input)?;
+        let (input, serial) = describe_nom_result(full, parse_header(input))?;
+
+        let engine = match self.engine.take() {
+            Some(e) if e.version == serial as FormatVersion => e,
+            _ => Engine::new_for_version(serial as FormatVersion)?,
+        };
+
+        let (_remainder, result) =
+            describe_nom_result(full, parse_body(engine, input, self, progress))?;
+        Ok(result)
+    }
+
+    /// Take back the storage of a format that's no longer needed, so that
+    /// later parses can reuse it.
+    pub fn recycle(&mut self, format: Format) {
+        self.engine = Some(format.engine);
+        self.mem = format.mem.mem;
+        self.eqtb = format.eqtb.into_buffer();
+        self.cshash = format.cshash.into_buffer();
+    }
+}
+
+/// Parse the engine parameters that follow the header, returning `hash_high`.
+fn parse_parameters<'a>(engine: &Engine, input: &'a [u8]) -> ParseResult<'a, i32> {
     let mem_top = engine.symbols.lookup("MEM_TOP") as i32;
     let eqtb_size = engine.symbols.lookup("EQTB_SIZE") as i32;
-    let hash_prime = engine.symbols.lookup("HASH_PRIME") as i32;
-    let hash_base = engine.symbols.lookup("HASH_BASE") as i32;
     let eqtb_top = engine.symbols.lookup("EQTB_TOP") as i32;
-    let prim_size = engine.symbols.lookup("PRIM_SIZE") as i32;
-    let max_fonts = engine.symbols.lookup("MAX_FONT_MAX") as i32;
+    let hash_prime = engine.symbols.lookup("HASH_PRIME") as i32;
 
-    let (input, hash_high) = be_i32(input)?;
+    // Our arrays are sized assuming the maximal `hash_extra`.
+    let (input, hash_high) = parseutils::ranged_be_i32(0, eqtb_top - eqtb_size)(input)?;
     let (input, _mem_top) = parseutils::satisfy_be_i32(mem_top)(input)?;
     let (input, _eqtb_size) = parseutils::satisfy_be_i32(eqtb_size)(input)?;
     let (input, _hash_prime) = parseutils::satisfy_be_i32(hash_prime as i32)(input)?;
-    let (input, _hyph_prime) = be_i32(input)?;
+    let (input, _hyph_prime) = parseutils::satisfy_be_i32(hyphenation::HYPH_PRIME)(input)?;
+    Ok((input, hash_high))
+}
+
+fn parse_fonts_only<'a>(engine: &Engine, input: &'a [u8]) -> ParseResult<'a, fonts::FontTable> {
+    let prim_size = engine.symbols.lookup("PRIM_SIZE") as usize;
+
+    let (input, hash_high) = parse_parameters(engine, input)?;
+    let (input, _) = stringtable::StringTable::skip(input)?;
+    let (input, lo_mem_max) = mem::Memory::skip(input, engine)?;
+    let (input, _) = eqtb::EquivalenciesTable::skip(input, engine, hash_high)?;
+    let (input, _par_loc) = be_i32(input)?;
+    let (input, _write_loc) = be_i32(input)?;
+    let (input, _prims) = take((prim_size + 1) * 8)(input)?;
+    let (input, _) = cshash::ControlSeqHash::skip(input, engine, hash_high)?;
+    fonts::FontTable::parse(input, engine, lo_mem_max)
+}
+
+fn parse_body<'a>(
+    engine: Engine,
+    input: &'a [u8],
+    scratch: &mut FormatParser,
+    progress: &mut dyn FnMut(ParseStage, usize),
+) -> ParseResult<'a, Format> {
+    let hash_base = engine.symbols.lookup("HASH_BASE") as i32;
+    let eqtb_top = engine.symbols.lookup("EQTB_TOP") as i32;
+    let prim_size = engine.symbols.lookup("PRIM_SIZE") as i32;
+
+    let (input, hash_high) = parse_parameters(&engine, input)?;
 
     // string table
+    //
+    // The format identification banner, `format_ident` (" (preloaded
+    // format=...)"), isn't among the strings: the engine builds it just before
+    // dumping, then removes it from the pool again, and it's regenerated from
+    // the job name when the format is loaded. So there's no way to recover it
+    // from a format file.
 
     let (input, strings) = stringtable::StringTable::parse(input)?;
+    progress(ParseStage::StringTable, input.len());
 
     // "mem" array
 
-    let (input, mem) = mem::Memory::parse(input, &engine)?;
+    let (input, mem) = mem::Memory::parse(input, &engine, std::mem::take(&mut scratch.mem))?;
+    progress(ParseStage::Mem, input.len());
 
     // eqtb
 
-    let (input, eqtb) = eqtb::EquivalenciesTable::parse(input, &engine, hash_high)?;
+    let (input, eqtb) = eqtb::EquivalenciesTable::parse(
+        input,
+        &engine,
+        hash_high,
+        std::mem::take(&mut scratch.eqtb),
+    )?;
+    progress(ParseStage::Eqtb, input.len());
 
     // nominally hash_top, but hash_top = eqtb_top since hash_extra is nonzero
     let (input, _par_loc) = parseutils::ranged_be_i32(hash_base as i32, eqtb_top as i32)(input)?;
 
+    // Note that despite its name, `write_loc` is just the location of the
+    // `\write` control sequence. The open/closed state of the `\read` and
+    // `\write` streams isn't saved in the format at all: the engine marks all
+    // of them as closed when it starts up, so a format never carries open
+    // streams from its build process.
     let (input, _write_loc) = parseutils::ranged_be_i32(hash_base as i32, eqtb_top as i32)(input)?;
 
-    // Primitives. TODO: figure out best type for `prims`.
+    // Primitives. Each entry is a (text, next) pair.
 
-    let (input, _prims) = count(be_i64, prim_size as usize + 1)(input)?;
+    let (input, prims) = count(pair(be_i32, be_i32), prim_size as usize + 1)(input)?;
+    let primitives = index_primitives(&engine, &strings, &prims);
 
     // Control sequence names -- the hash table.
 
-    let (input, cshash) = cshash::ControlSeqHash::parse(input, &engine, hash_high)?;
+    let (input, cshash) = cshash::ControlSeqHash::parse(
+        input,
+        &engine,
+        hash_high,
+        std::mem::take(&mut scratch.cshash),
+    )?;
+    progress(ParseStage::Hash, input.len());
 
     // font info
 
-    let (input, fmem_ptr) = parseutils::ranged_be_i32(7, 147483647)(input)?;
-
-    let (input, _font_info) = count(be_i64, fmem_ptr as usize)(input)?;
-
-    // NB: FONT_BASE = 0
-    let (input, font_ptr) = parseutils::ranged_be_i32(0, max_fonts as i32)(input)?;
-
-    let n_fonts = font_ptr as usize + 1;
-    let (input, _font_check) = count(be_i64, n_fonts)(input)?;
-    let (input, _font_size) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_dsize) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_params) = count(
-        parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD),
-        n_fonts,
-    )(input)?;
-    let (input, _hyphen_char) = count(be_i32, n_fonts)(input)?;
-    let (input, _skew_char) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_name) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_area) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_bc) = count(be_i16, n_fonts)(input)?;
-    let (input, _font_ec) = count(be_i16, n_fonts)(input)?;
-    let (input, _char_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _width_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _height_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _depth_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _italic_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _lig_kern_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _kern_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _exten_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _param_base) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_glue) = count(
-        parseutils::ranged_be_i32(MIN_HALFWORD, mem.lo_mem_max),
-        n_fonts,
-    )(input)?;
-    let (input, _bchar_label) = count(parseutils::ranged_be_i32(0, fmem_ptr - 1), n_fonts)(input)?;
-    let (input, _font_bchar) = count(parseutils::ranged_be_i32(0, TOO_BIG_CHAR), n_fonts)(input)?;
-    let (input, _font_false_bchar) =
-        count(parseutils::ranged_be_i32(0, TOO_BIG_CHAR), n_fonts)(input)?;
+    let (input, fonts) = fonts::FontTable::parse(input, &engine, mem.lo_mem_max)?;
+    progress(ParseStage::Fonts, input.len());
 
     // Hyphenations!
 
-    let (input, hyph_count) = be_i32(input)?;
-
-    let (input, mut _hyph_next) = be_i32(input)?;
-
-    let mut hyph_next;
-    let mut hyph_link = vec![0u16; HYPH_SIZE];
-    let mut hyph_word = vec![0i32; HYPH_SIZE];
-    let mut hyph_list = vec![0i32; HYPH_SIZE];
-    let mut input = input;
     let max_word = strings.len() as i32 + TOO_BIG_CHAR - 1;
+    let (input, hyph_exceptions) = hyphenation::HyphenationExceptions::parse(input, max_word)?;
+    progress(ParseStage::Hyphenation, input.len());
 
-    for _ in 0..hyph_count {
-        let (ii, mut j) = be_i32(input)?;
-
-        if j > 0xFFFF {
-            hyph_next = j / 0x10000;
-            j -= hyph_next * 0x10000;
-        } else {
-            hyph_next = 0;
-        }
+    // trie
 
-        hyph_link[j as usize] = hyph_next as u16;
+    let (input, trie) = hyphenation::HyphenationTrie::parse(input)?;
+    progress(ParseStage::Trie, input.len());
 
-        let (ii, w) = parseutils::ranged_be_i32(0, max_word)(ii)?;
-        hyph_word[j as usize] = w;
+    // All done! Classic TeX dumps the `interaction` mode and `format_ident`
+    // just before the footer, but Tectonic's engine doesn't: the interaction
+    // mode is always reset to `\errorstopmode` at startup, so formats don't
+    // record the mode that they were dumped in.
 
-        let (ii, l) = parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD)(ii)?;
-        hyph_list[j as usize] = l;
+    let (input, footer_ok) = match parseutils::satisfy_be_i32(FOOTER_MAGIC)(input) {
+        Ok((input, _)) => (input, true),
+        Err(_) if scratch.tolerant => (input, false),
+        Err(e) => return Err(e),
+    };
 
-        input = ii;
-    }
+    let fmt = Format {
+        engine,
+        strings,
+        mem,
+        eqtb,
+        cshash,
+        primitives,
+        fonts,
+        hyph_exceptions,
+        trie,
+        trailing_bytes: input.len(),
+        footer_ok,
+    };
+    Ok((input, fmt))
+}
 
-    // trie
+/// Index the primitives saved in the format by their command codes and
+/// arguments.
+///
+/// The format only records the names of the primitives, so the codes come from
+/// the engine's definitions. Names that the engine doesn't know are skipped.
+/// Synonyms that share a code are listed in the engine's definition order, so
+/// that the first name is the canonical one.
+fn index_primitives(
+    engine: &Engine,
+    strings: &stringtable::StringTable,
+    prims: &[(i32, i32)],
+) -> BTreeMap<(CommandCode, CommandArgument), Vec<String>> {
+    let codes: HashMap<&str, (usize, CommandCode, CommandArgument)> = engine
+        .primitives()
+        .enumerate()
+        .map(|(rank, (cmd, chr, name))| (name, (rank, cmd, chr)))
+        .collect();
+    let max_sp = strings.len() as i32 + TOO_BIG_CHAR - 1;
+
+    let mut found: Vec<_> = prims
+        .iter()
+        .filter_map(|(text, _next)| {
+            // The text field is offset by one, so that zero means "unused".
+            let s = text.checked_sub(1)?;
+
+            let name = if s > max_sp || s < 0 {
+                return None;
+            } else if s >= TOO_BIG_CHAR {
+                strings.lookup(s).to_owned()
+            } else {
+                char::from_u32(s as u32)?.to_string()
+            };
 
-    let (input, trie_max) = be_i32(input)?;
+            codes.get(name.as_str()).map(|code| (*code, name))
+        })
+        .collect();
+    found.sort();
 
-    let (input, _hyph_start) = parseutils::ranged_be_i32(0, trie_max)(input)?;
+    let mut primitives: BTreeMap<_, Vec<String>> = BTreeMap::new();
 
-    let n_trie = trie_max as usize + 1;
-    let (input, _trie_trl) = count(be_i32, n_trie)(input)?;
-    let (input, _trie_tro) = count(be_i32, n_trie)(input)?;
-    let (input, _trie_trc) = count(be_u16, n_trie)(input)?;
+    for ((_rank, cmd, chr), name) in found {
+        primitives.entry((cmd, chr)).or_default().push(name);
+    }
 
-    let (input, _max_hyph_char) = be_i32(input)?;
+    primitives
+}
 
-    let (input, trie_op_ptr) = parseutils::ranged_be_i32(0, TRIE_OP_SIZE)(input)?;
+/// Render and merge the names listed by [`Format::control_sequence_names`].
+///
+/// Active characters are rendered with their code points, so they don't
+/// collide with the single-character control sequences. If a control sequence
+/// name does render the same as an active character, the one entry that is
+/// kept stands for the active character.
+fn merge_control_sequence_names<C, A>(cseqs: C, actives: A, escape_char: i32) -> Vec<String>
+where
+    C: Iterator<Item = String>,
+    A: Iterator<Item = char>,
+{
+    let esc = char::from_u32(escape_char as u32).map_or_else(String::new, |c| c.to_string());
+
+    let names: BTreeSet<String> = cseqs
+        .map(|name| format!("{}{}", esc, name))
+        .chain(actives.map(|c| fmt_usv(c as i32)))
+        .collect();
+
+    names.into_iter().collect()
+}
 
-    // IMPORTANT!!! XeTeX loads these into 1-based indices!
-    let (input, _hyf_distance) = count(be_i16, trie_op_ptr as usize)(input)?;
-    let (input, _hyf_num) = count(be_i16, trie_op_ptr as usize)(input)?;
-    let (input, _hyf_next) = count(be_u16, trie_op_ptr as usize)(input)?;
+/// Compute the signature described in [`Format::primitive_set_signature`].
+fn primitive_set_signature<'a, I: Iterator<Item = &'a str>>(names: I) -> u64 {
+    let names: BTreeSet<&str> = names.collect();
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
 
-    let mut trie_used = vec![0i32; BIGGEST_LANG + 1];
-    let mut op_start = vec![0i32; BIGGEST_LANG + 1];
+    for name in names {
+        for b in name.bytes().chain(std::iter::once(0)) {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
 
-    let mut k = BIGGEST_LANG + 1;
-    let mut j = trie_op_ptr;
-    let mut input = input;
+    hash
+}
 
-    while j > 0 {
-        let (ii, new_k) = parseutils::ranged_be_i32(0, k as i32 - 1)(input)?;
-        k = new_k as usize;
-        let (ii, u) = parseutils::ranged_be_i32(1, j)(ii)?;
-        trie_used[k] = u;
-        j -= u;
-        op_start[k] = j;
-        input = ii;
+/// Normalize a language number the way TeX does.
+fn norm_lang(lang: usize) -> usize {
+    if lang > hyphenation::BIGGEST_LANG {
+        0
+    } else {
+        lang
     }
+}
 
-    // All done!
-
-    let (input, _) = parseutils::satisfy_be_i32(FOOTER_MAGIC)(input)?;
+/// Normalize a `\lefthyphenmin` or `\righthyphenmin` value, following
+/// `norm_min()` in the engine.
+fn norm_min(h: i32) -> usize {
+    h.clamp(1, 63) as usize
+}
 
-    let fmt = Format {
-        engine,
-        strings,
-        mem,
-        eqtb,
-        cshash,
-    };
-    Ok((input, fmt))
+/// Convert hyphenation values, indexed by UTF-16 code, into the positions of
+/// allowed hyphens, counted in characters. *ends* gives the number of codes
+/// through the end of each character.
+fn hyf_positions(hyf: &[i16], ends: &[usize]) -> Vec<usize> {
+    ends.iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(hyf.get(**e), Some(h) if h % 2 == 1))
+        .map(|(i, _)| i + 1)
+        .collect()
 }
 
 fn valid_usvs() -> impl Iterator<Item = i32> {
     (0..0xD800).chain(0xE000..0x11_0000)
 }
 
+/// Fold a name so that names that look alike become equal.
+///
+/// Fullwidth ASCII forms are mapped to ASCII, Greek and Cyrillic letters that
+/// look like Latin ones are mapped to those, as are some common variant
+/// spaces, dashes, quotes, and tildes; then the result is lowercased and
+/// combining diacritical marks are dropped. This is only a heuristic, much
+/// smaller than the full Unicode confusables data.
+pub fn confusable_skeleton(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+            _ => CONFUSABLES
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to),
+        })
+        .flat_map(char::to_lowercase)
+        .filter(|c| !('\u{300}'..='\u{36F}').contains(c))
+        .collect()
+}
+
 pub fn fmt_usv(c: i32) -> String {
     // Valid inputs are valid USVs, which are as per the Unicode Glossary: "Any
     // Unicode code point except high-surrogate and low-surrogate code points.
@@ -497,13 +3217,180 @@ pub fn fmt_usv(c: i32) -> String {
 }
 
 pub fn fmt_csname<S: AsRef<str>>(name: S) -> String {
+    fmt_csname_with_escape(name, '\\' as i32)
+}
+
+/// Format a control-sequence name as [`fmt_csname`] does, but using
+/// *escape_char* as the escape character, as TeX does with `\escapechar`.
+///
+/// If *escape_char* isn't a valid character, such as when it's -1, the name
+/// is rendered without any escape character at all.
+pub fn fmt_csname_with_escape<S: AsRef<str>>(name: S, escape_char: i32) -> String {
     let name = name.as_ref();
     let has_ws = name.contains(char::is_whitespace);
+    let esc = char::from_u32(escape_char as u32).map_or_else(String::new, |c| c.to_string());
 
     match (name.len(), has_ws) {
         (0, _) => "[null CS]".to_owned(),
-        (1, _) => format!("'\\{}'", fmt_usv(name.chars().next().unwrap() as i32)),
-        (_, false) => format!("\\{}", name),
-        (_, true) => format!("\"\\{}\"", name),
+        (1, _) => format!("'{}{}'", esc, fmt_usv(name.chars().next().unwrap() as i32)),
+        (_, false) => format!("{}{}", esc, name),
+        (_, true) => format!("\"{}{}\"", esc, name),
+    }
+}
+
+/// Format a font identifier as described in [`Format::font_identifier`].
+fn fmt_font_identifier(name: String, size: Fixed, design_size: Fixed) -> String {
+    if size != design_size {
+        format!("{} at {}", name, size)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdftex_compat_names() {
+        let engine = Engine::default();
+
+        for name in PDFTEX_COMPAT_PRIMITIVES.iter().chain(GRAPHICS_PRIMITIVES) {
+            assert!(
+                engine.primitives().any(|(_, _, n)| n == *name),
+                "{} is not a primitive",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn confusables() {
+        assert_eq!(confusable_skeleton("relax"), "relax");
+        assert_eq!(confusable_skeleton("\u{FF32}elax"), "relax");
+        assert_eq!(confusable_skeleton("\u{43E}"), "o");
+        assert_eq!(confusable_skeleton("\u{39F}"), "o");
+        assert_eq!(confusable_skeleton("e\u{301}"), "e");
+        assert_eq!(confusable_skeleton("\u{223C}"), "~");
+        assert_ne!(confusable_skeleton("\u{3B7}"), "h");
+
+        for (from, to) in CONFUSABLES {
+            assert_ne!(from, to);
+            assert!(to.is_ascii());
+        }
+    }
+
+    #[test]
+    fn primitive_signature() {
+        assert_eq!(
+            primitive_set_signature(std::iter::empty()),
+            0xcbf2_9ce4_8422_2325
+        );
+
+        let a = primitive_set_signature(["relax", "par", "relax"].iter().copied());
+        let b = primitive_set_signature(["par", "relax"].iter().copied());
+        let c = primitive_set_signature(["parrelax"].iter().copied());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn format_is_shareable() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Format>();
+    }
+
+    #[test]
+    fn escape_char() {
+        assert_eq!(fmt_csname("relax"), "\\relax");
+        assert_eq!(fmt_csname_with_escape("relax", '@' as i32), "@relax");
+        assert_eq!(fmt_csname_with_escape("relax", -1), "relax");
+        assert_eq!(fmt_csname_with_escape("", -1), "[null CS]");
+    }
+
+    #[test]
+    fn sniff() {
+        let header = |magic: i32, serial: i32| {
+            let mut v = magic.to_be_bytes().to_vec();
+            v.extend_from_slice(&serial.to_be_bytes());
+            v
+        };
+
+        assert!(Format::is_probably_format(&header(HEADER_MAGIC, 32)));
+        assert!(!Format::is_probably_format(&header(HEADER_MAGIC, 0)));
+        assert!(!Format::is_probably_format(&header(HEADER_MAGIC, 9999)));
+        assert!(!Format::is_probably_format(&header(0x1f8b_0800, 32)));
+        assert!(!Format::is_probably_format(&header(HEADER_MAGIC, 32)[..6]));
+    }
+
+    #[test]
+    fn control_sequence_name_namespaces() {
+        let cseqs = ["~", "relax", "relax"].iter().map(|s| s.to_string());
+        let names = |esc| merge_control_sequence_names(cseqs.clone(), "~".chars(), esc);
+
+        assert_eq!(names('\\' as i32), ["\\relax", "\\~", "~ (0x00007e)"]);
+        assert_eq!(names(-1), ["relax", "~", "~ (0x00007e)"]);
+    }
+
+    #[test]
+    fn font_identifiers() {
+        let id = |size, design_size| {
+            fmt_font_identifier("cmr10".to_owned(), Fixed(size), Fixed(design_size))
+        };
+
+        assert_eq!(id(10 << 16, 10 << 16), "cmr10");
+        assert_eq!(id(12 << 16, 10 << 16), "cmr10 at 12.0pt");
+        assert_eq!(id(0x8000, 10 << 16), "cmr10 at 0.5pt");
+    }
+
+    #[test]
+    fn index_corrupt_primitives() {
+        let engine = Engine::default();
+        let strings = stringtable::StringTable::from_strings(vec![
+            "relax".to_owned(),
+            "XeTeXmathchardef".to_owned(),
+            "Umathchardef".to_owned(),
+        ]);
+        let prims = [
+            (i32::MIN, 0),
+            (0, 0),
+            (i32::MAX, 0),
+            (TOO_BIG_CHAR + 1, 0),
+            (TOO_BIG_CHAR + 2, 0),
+            (TOO_BIG_CHAR + 3, 0),
+        ];
+
+        let index = index_primitives(&engine, &strings, &prims);
+        let names: Vec<_> = index.values().flatten().map(|s| s.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"relax"));
+
+        let synonyms = index
+            .values()
+            .find(|names| names.len() == 2)
+            .expect("synonyms should share a code");
+        assert_eq!(synonyms, &["Umathchardef", "XeTeXmathchardef"]);
+    }
+
+    #[test]
+    fn too_short() {
+        for len in &[0, 8, MIN_HEADER_SIZE - 1] {
+            let err = Format::parse(&vec![0; *len][..]).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("input too short to be a format file ({} bytes)", len)
+            );
+        }
+
+        assert!(Format::try_from(&[0u8; 8][..]).is_err());
+        assert!(Format::try_from(vec![0u8; 8]).is_err());
+    }
+
+    #[test]
+    fn gzipped_bundle_entry() {
+        let mut data = [0; 64];
+        data[..2].copy_from_slice(&GZIP_MAGIC);
+        let err = Format::parse_bundle_entry(&data).unwrap_err();
+        assert!(err.to_string().contains("gzip-compressed"));
     }
 }
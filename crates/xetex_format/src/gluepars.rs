@@ -14,7 +14,7 @@ use crate::{
 };
 
 /// Different kinds of glue parameters.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum GlueParKind {
     /// A regular glue parameter.
     Regular,
@@ -24,7 +24,7 @@ pub enum GlueParKind {
 }
 
 /// Information about glue parameters.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct GluePar {
     /// The name of the parameter.
     name: &'static str,
@@ -36,6 +36,29 @@ pub struct GluePar {
     since: FormatVersion,
 }
 
+impl GluePar {
+    /// Get the name of this parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the kind of this parameter.
+    pub fn kind(&self) -> GlueParKind {
+        self.kind
+    }
+
+    /// Get the name of the primitive associated with this parameter.
+    pub fn primitive_name(&self) -> String {
+        self.name.replace('_', "")
+    }
+
+    /// Get the name of the symbol giving this parameter's offset from
+    /// `GLUE_BASE`.
+    pub fn symbol_name(&self) -> String {
+        format!("GLUE_PAR__{}", self.name.to_lowercase())
+    }
+}
+
 const GLUE_PARS: &[GluePar] = &[
     GluePar {
         name: "line_skip",
@@ -146,11 +169,7 @@ pub fn get_gluepars_for_version(
     for p in GLUE_PARS {
         if version >= p.since {
             r.push(*p);
-            symbols.add(
-                SymbolCategory::GluePars,
-                format!("GLUE_PAR__{}", p.name.to_lowercase()),
-                n,
-            )?;
+            symbols.add(SymbolCategory::GluePars, p.symbol_name(), n)?;
             n += 1;
         }
     }
@@ -169,10 +188,10 @@ pub fn emit_c_header_primitives<W: Write>(pars: &[GluePar], mut stream: W) -> Re
 
         writeln!(
             stream,
-            "    {{ \"{}\", {}, GLUE_BASE + GLUE_PAR__{}, xf_prim_init_none }}, \\",
-            par.name.replace('_', ""),
+            "    {{ \"{}\", {}, GLUE_BASE + {}, xf_prim_init_none }}, \\",
+            par.primitive_name(),
             cmd,
-            par.name.to_lowercase(),
+            par.symbol_name(),
         )?;
     }
 
@@ -0,0 +1,502 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Hyphenation data: the exception dictionary and the pattern trie.
+//!
+//! Hyphenation exceptions (set up with `\hyphenation`) are stored in a hash
+//! table of `HYPH_SIZE` slots. Each occupied slot holds a string pointer to the
+//! exception word and a pointer to a linked list in `mem` giving the hyphen
+//! positions. Slots are chained through `hyph_link`.
+//!
+//! Hyphenation patterns (set up with `\patterns`) are compiled into a packed
+//! trie along with a table of "ops" describing the hyphenation values that
+//! apply at different positions.
+
+use nom::{
+    multi::count,
+    number::complete::{be_i16, be_i32, be_u16},
+};
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use crate::{
+    base::{MAX_HALFWORD, MIN_HALFWORD},
+    mem::MemPointer,
+    parseutils::{self, ParseResult},
+    stringtable::{StrPointer, StringTable, TOO_BIG_CHAR},
+};
+
+/// The number of slots in the hyphenation exception table.
+pub const HYPH_SIZE: usize = 8191;
+
+/// The prime used to hash words into the exception table.
+pub const HYPH_PRIME: i32 = 607;
+
+/// The maximum number of trie ops.
+pub const TRIE_OP_SIZE: i32 = 35111;
+
+/// The largest valid language number.
+pub const BIGGEST_LANG: usize = 255;
+
+/// A word whose hyphenation didn't come out as expected, as reported by
+/// [`crate::format::Format::check_hyphenation`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HyphenationMismatch {
+    /// The word that was hyphenated.
+    pub word: String,
+
+    /// The expected hyphen positions.
+    pub expected: Vec<usize>,
+
+    /// The hyphen positions that were actually found.
+    pub actual: Vec<usize>,
+}
+
+/// Summary statistics about the hyphenation patterns of a language, as
+/// computed by [`HyphenationTrie::pattern_stats`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PatternStats {
+    /// The number of distinct patterns.
+    pub patterns: usize,
+
+    /// The length of the shortest pattern, or zero if there are none.
+    pub min_length: usize,
+
+    /// The length of the longest pattern, or zero if there are none.
+    pub max_length: usize,
+
+    /// The hyphenation codes that the patterns refer to, in increasing order.
+    pub codes: Vec<u16>,
+}
+
+/// The hyphenation exception dictionary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HyphenationExceptions {
+    /// The number of exceptions, as recorded in the format.
+    pub count: i32,
+
+    /// The overflow pointer of the table, as recorded in the format. When
+    /// words collide in the hash, the engine searches for a free slot starting
+    /// from this point.
+    pub next: i32,
+
+    /// The chaining links between slots.
+    pub link: Vec<u16>,
+
+    /// The exception words, as string pointers; zero for an empty slot. Use
+    /// [`HyphenationExceptions::word_text`] to decode them.
+    pub word: Vec<StrPointer>,
+
+    /// The hyphen position lists, as pointers into `mem`.
+    pub list: Vec<MemPointer>,
+}
+
+impl HyphenationExceptions {
+    pub(crate) fn parse(input: &[u8], max_word: StrPointer) -> ParseResult<'_, Self> {
+        let (input, hyph_count) = be_i32(input)?;
+        let (input, hyph_next) = parseutils::ranged_be_i32(HYPH_PRIME, HYPH_SIZE as i32)(input)?;
+
+        let mut link = vec![0u16; HYPH_SIZE];
+        let mut word = vec![0i32; HYPH_SIZE];
+        let mut list = vec![0i32; HYPH_SIZE];
+        let mut input = input;
+
+        for _ in 0..hyph_count {
+            let (ii, mut j) = be_i32(input)?;
+
+            let next = if j > 0xFFFF {
+                let next = j / 0x10000;
+                j -= next * 0x10000;
+                next
+            } else {
+                0
+            };
+
+            parseutils::check_range(input, j, 0, HYPH_SIZE as i32 - 1)?;
+            parseutils::check_range(input, next, 0, HYPH_SIZE as i32)?;
+            link[j as usize] = next as u16;
+
+            let (ii, w) = parseutils::ranged_be_i32(0, max_word)(ii)?;
+            word[j as usize] = w;
+
+            let (ii, l) = parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD)(ii)?;
+            list[j as usize] = l;
+
+            input = ii;
+        }
+
+        Ok((
+            input,
+            HyphenationExceptions {
+                count: hyph_count,
+                next: hyph_next,
+                link,
+                word,
+                list,
+            },
+        ))
+    }
+
+    /// Get the number of slots in the table that hold words.
+    pub fn slots_used(&self) -> usize {
+        self.word.iter().filter(|w| **w != 0).count()
+    }
+
+    /// Get the text of the exception word stored in slot *h*, or None if the
+    /// slot is empty.
+    ///
+    /// The text consists of the word's hyphenation codes followed by its
+    /// language number, encoded as a character.
+    pub fn word_text(&self, h: usize, strings: &StringTable) -> Option<String> {
+        decode_word(*self.word.get(h)?, strings)
+    }
+
+    /// Look up a word in the exception dictionary, returning the pointer to
+    /// its list of hyphen positions in `mem`.
+    ///
+    /// The word should be given as it's stored in the dictionary: as UTF-16
+    /// hyphenation codes, followed by the language number.
+    pub fn lookup(&self, word: &[u16], strings: &StringTable) -> Option<MemPointer> {
+        let (first, rest) = word.split_first()?;
+        let mut h =
+            rest.iter()
+                .fold(*first as i32, |h, c| (h + h + *c as i32) % HYPH_PRIME) as usize;
+
+        loop {
+            let k = *self.word.get(h)?;
+
+            if k == 0 {
+                return None;
+            }
+
+            if let Some(text) = decode_word(k, strings) {
+                if text.encode_utf16().eq(word.iter().copied()) {
+                    return Some(self.list[h]);
+                }
+            }
+
+            match self.link[h] {
+                0 => return None,
+                next => h = next as usize - 1,
+            }
+        }
+    }
+}
+
+/// Decode a `hyph_word` value.
+///
+/// Like other TeX string numbers, values of `TOO_BIG_CHAR` and above refer to
+/// the string table, while smaller values denote single-character strings.
+/// Zero marks an empty slot.
+fn decode_word(w: StrPointer, strings: &StringTable) -> Option<String> {
+    if w >= TOO_BIG_CHAR {
+        strings.strings.get((w - TOO_BIG_CHAR) as usize).cloned()
+    } else if w > 0 {
+        char::from_u32(w as u32).map(|c| c.to_string())
+    } else {
+        None
+    }
+}
+
+/// The compiled hyphenation pattern trie.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HyphenationTrie {
+    /// The largest index used in the trie arrays.
+    pub max: i32,
+
+    /// The start of the "hyphenation codes" pseudo-language, for
+    /// `\savinghyphcodes`.
+    pub hyph_start: i32,
+
+    /// The `trie_link` fields of the trie.
+    pub trl: Vec<i32>,
+
+    /// The `trie_op` fields of the trie.
+    pub tro: Vec<i32>,
+
+    /// The `trie_char` fields of the trie.
+    pub trc: Vec<u16>,
+
+    /// The largest character code for which hyphenation patterns were given.
+    pub max_hyph_char: i32,
+
+    /// The number of trie ops.
+    pub op_ptr: i32,
+
+    /// The `hyf_distance` values of the ops. NB: XeTeX indexes these from one!
+    pub hyf_distance: Vec<i16>,
+
+    /// The `hyf_num` values of the ops. NB: XeTeX indexes these from one!
+    pub hyf_num: Vec<i16>,
+
+    /// The `hyf_next` values of the ops. NB: XeTeX indexes these from one!
+    pub hyf_next: Vec<u16>,
+
+    /// The number of ops used by each language.
+    pub trie_used: Vec<i32>,
+
+    /// The offset of each language's first op.
+    pub op_start: Vec<i32>,
+}
+
+impl HyphenationTrie {
+    pub(crate) fn parse(input: &[u8]) -> ParseResult<'_, Self> {
+        let (input, trie_max) = parseutils::ranged_be_i32(0, i32::MAX - 1)(input)?;
+
+        let (input, hyph_start) = parseutils::ranged_be_i32(0, trie_max)(input)?;
+
+        let n_trie = trie_max as usize + 1;
+        let (input, trl) = parseutils::sized_count(be_i32, n_trie, 4)(input)?;
+        let (input, tro) = parseutils::sized_count(be_i32, n_trie, 4)(input)?;
+        let (input, trc) = parseutils::sized_count(be_u16, n_trie, 2)(input)?;
+
+        let (input, max_hyph_char) = be_i32(input)?;
+
+        let (input, trie_op_ptr) = parseutils::ranged_be_i32(0, TRIE_OP_SIZE)(input)?;
+
+        let (input, hyf_distance) = count(be_i16, trie_op_ptr as usize)(input)?;
+        let (input, hyf_num) = count(be_i16, trie_op_ptr as usize)(input)?;
+        let (input, hyf_next) = count(be_u16, trie_op_ptr as usize)(input)?;
+
+        let mut trie_used = vec![0i32; BIGGEST_LANG + 1];
+        let mut op_start = vec![0i32; BIGGEST_LANG + 1];
+
+        let mut k = BIGGEST_LANG + 1;
+        let mut j = trie_op_ptr;
+        let mut input = input;
+
+        while j > 0 {
+            let (ii, new_k) = parseutils::ranged_be_i32(0, k as i32 - 1)(input)?;
+            k = new_k as usize;
+            let (ii, u) = parseutils::ranged_be_i32(1, j)(ii)?;
+            trie_used[k] = u;
+            j -= u;
+            op_start[k] = j;
+            input = ii;
+        }
+
+        Ok((
+            input,
+            HyphenationTrie {
+                max: trie_max,
+                hyph_start,
+                trl,
+                tro,
+                trc,
+                max_hyph_char,
+                op_ptr: trie_op_ptr,
+                hyf_distance,
+                hyf_num,
+                hyf_next,
+                trie_used,
+                op_start,
+            },
+        ))
+    }
+    /// Compute summary statistics about the hyphenation patterns of
+    /// language *lang*.
+    ///
+    /// This walks every path through the language's part of the trie, so the
+    /// patterns are counted correctly even where the engine has merged
+    /// identical subtries. The word-boundary markers (the `.`s of the
+    /// original patterns) aren't counted in the lengths or included in the
+    /// codes.
+    pub fn pattern_stats(&self, lang: usize) -> PatternStats {
+        // Patterns are never longer than this; it bounds the walk in case the
+        // trie is corrupt.
+        const MAX_DEPTH: usize = 256;
+
+        let mut stats = PatternStats::default();
+
+        if self.trc.get(lang + 1).map(|c| *c as usize) != Some(lang) {
+            return stats;
+        }
+
+        let n_codes = self.max_hyph_char.max(0) as usize;
+        let mut codes = BTreeSet::new();
+
+        // Each entry is the link of a node, the number of non-boundary codes
+        // on the path to it, and the length of the path.
+        let mut stack = vec![(self.trl[lang + 1], 0, 0)];
+
+        while let Some((base, len, depth)) = stack.pop() {
+            if base <= 0 {
+                continue;
+            }
+
+            for c in 0..n_codes {
+                let z = base as usize + c;
+
+                if self.trc.get(z).map(|tc| *tc as usize) != Some(c) {
+                    continue;
+                }
+
+                let len = if c == 0 { len } else { len + 1 };
+
+                if c != 0 {
+                    codes.insert(c as u16);
+                }
+
+                if self.tro[z] != 0 {
+                    if stats.patterns == 0 || len < stats.min_length {
+                        stats.min_length = len;
+                    }
+
+                    stats.max_length = stats.max_length.max(len);
+                    stats.patterns += 1;
+                }
+
+                if depth < MAX_DEPTH {
+                    stack.push((self.trl[z], len, depth + 1));
+                }
+            }
+        }
+
+        stats.codes = codes.into_iter().collect();
+        stats
+    }
+
+    /// Apply the hyphenation patterns of language *lang* to a word.
+    ///
+    /// The word should be given as UTF-16 hyphenation codes. The result has
+    /// one more element than the word: element *j* is the hyphenation value for
+    /// the position after the *j*'th code, and an odd value allows a hyphen
+    /// there. No limits on the hyphen positions (a la `\lefthyphenmin`) are
+    /// applied. If the language has no patterns, all of the values are zero.
+    pub fn hyphenation_values(&self, lang: usize, word: &[u16]) -> Vec<i16> {
+        let hn = word.len();
+        let mut hyf = vec![0i16; hn + 1];
+
+        if self.trc.get(lang + 1).map(|c| *c as usize) != Some(lang) {
+            return hyf;
+        }
+
+        // The word is bracketed by boundary characters, and ends with a
+        // sentinel that the trie never matches.
+        let mut hc = Vec::with_capacity(hn + 3);
+        hc.push(0);
+        hc.extend(word.iter().map(|c| *c as i32));
+        hc.push(0);
+        hc.push(self.max_hyph_char);
+
+        let op_start = self.op_start.get(lang).copied().unwrap_or(0);
+        let trl = |z: i32| {
+            usize::try_from(z)
+                .ok()
+                .and_then(|z| self.trl.get(z).copied())
+        };
+        let trc = |z: i32| {
+            usize::try_from(z)
+                .ok()
+                .and_then(|z| self.trc.get(z).copied())
+        };
+        let tro = |z: i32| {
+            usize::try_from(z)
+                .ok()
+                .and_then(|z| self.tro.get(z).copied())
+        };
+
+        for j in 0..=hn {
+            let mut l = j;
+            let mut z = match trl(lang as i32 + 1) {
+                Some(link) => link.saturating_add(hc[j]),
+                None => return hyf,
+            };
+
+            while l < hc.len() && trc(z).map(|c| c as i32) == Some(hc[l]) {
+                let mut v = tro(z).unwrap_or(0);
+
+                // Recall that XeTeX indexes the ops from one. Bound the number
+                // of steps in case the format is corrupt.
+                for _ in 0..self.op_ptr {
+                    if v == 0 {
+                        break;
+                    }
+
+                    let op = match usize::try_from(v.saturating_add(op_start) - 1) {
+                        Ok(op) if op < self.hyf_num.len() => op,
+                        _ => break,
+                    };
+
+                    let i = l as isize - self.hyf_distance[op] as isize;
+
+                    if let Some(h) = usize::try_from(i).ok().and_then(|i| hyf.get_mut(i)) {
+                        *h = (*h).max(self.hyf_num[op]);
+                    }
+
+                    v = self.hyf_next[op] as i32;
+                }
+
+                l += 1;
+
+                z = match (trl(z), hc.get(l)) {
+                    (Some(link), Some(c)) => link.saturating_add(*c),
+                    _ => break,
+                };
+            }
+        }
+
+        hyf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_stats() {
+        // Language 0 has the patterns `a1`, `a1b`, and `.b1`, with the codes
+        // of `a` and `b` being 1 and 2.
+        let mut trie = HyphenationTrie {
+            max: 39,
+            hyph_start: 0,
+            trl: vec![0; 40],
+            tro: vec![0; 40],
+            trc: vec![0; 40],
+            max_hyph_char: 3,
+            op_ptr: 1,
+            hyf_distance: vec![0],
+            hyf_num: vec![1],
+            hyf_next: vec![0],
+            trie_used: vec![0; BIGGEST_LANG + 1],
+            op_start: vec![0; BIGGEST_LANG + 1],
+        };
+
+        let nodes = [
+            (1, 0, 10, 0),
+            (10, 0, 30, 0),
+            (11, 1, 20, 1),
+            (22, 2, 0, 1),
+            (32, 2, 0, 1),
+        ];
+
+        for (z, c, link, op) in nodes.iter() {
+            trie.trc[*z] = *c;
+            trie.trl[*z] = *link;
+            trie.tro[*z] = *op;
+        }
+
+        let stats = trie.pattern_stats(0);
+        assert_eq!(stats.patterns, 3);
+        assert_eq!(stats.min_length, 1);
+        assert_eq!(stats.max_length, 2);
+        assert_eq!(stats.codes, vec![1, 2]);
+
+        assert_eq!(trie.pattern_stats(5), PatternStats::default());
+    }
+
+    #[test]
+    fn decode_words() {
+        let strings = StringTable::from_strings(vec!["a".to_owned(), "table\u{1}".to_owned()]);
+
+        assert_eq!(decode_word(0, &strings), None);
+        assert_eq!(decode_word('x' as i32, &strings), Some("x".to_owned()));
+        assert_eq!(decode_word(0xFFFF, &strings), Some("\u{FFFF}".to_owned()));
+        assert_eq!(decode_word(TOO_BIG_CHAR, &strings), Some("a".to_owned()));
+        assert_eq!(
+            decode_word(TOO_BIG_CHAR + 1, &strings),
+            Some("table\u{1}".to_owned())
+        );
+        assert_eq!(decode_word(TOO_BIG_CHAR + 2, &strings), None);
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! XeTeX's settings for interpreting input files.
+
+use crate::stringtable::{StringTable, TOO_BIG_CHAR};
+
+/// The encoding that XeTeX uses to read an input file.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum InputEncoding {
+    /// Sniff the encoding when the file is opened, choosing between UTF-8 and
+    /// the UTF-16 variants.
+    Auto,
+
+    /// UTF-8.
+    Utf8,
+
+    /// Big-endian UTF-16.
+    Utf16Be,
+
+    /// Little-endian UTF-16.
+    Utf16Le,
+
+    /// Raw bytes, each of which is taken as a character code.
+    Raw,
+
+    /// An ICU converter, identified by its name.
+    Icu(String),
+
+    /// An input mode not known to this crate.
+    Unknown(i32),
+}
+
+impl InputEncoding {
+    /// Decode an encoding from its mode number and the associated info
+    /// value, as stored in `\XeTeXdefaultencoding`'s integer parameters.
+    ///
+    /// For ICU converters, the info value is the string number of the
+    /// converter name.
+    pub fn decode(mode: i32, info: i32, strings: &StringTable) -> Self {
+        match mode {
+            0 => InputEncoding::Auto,
+            1 => InputEncoding::Utf8,
+            2 => InputEncoding::Utf16Be,
+            3 => InputEncoding::Utf16Le,
+            4 => InputEncoding::Raw,
+            5 => match strings
+                .strings
+                .get(info.wrapping_sub(TOO_BIG_CHAR) as usize)
+            {
+                Some(name) => InputEncoding::Icu(name.clone()),
+                None => InputEncoding::Unknown(mode),
+            },
+            _ => InputEncoding::Unknown(mode),
+        }
+    }
+}
+
+/// The Unicode normalization that XeTeX applies to input lines.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InputNormalization {
+    /// No normalization.
+    None,
+
+    /// Normalization Form C (composed).
+    Nfc,
+
+    /// Normalization Form D (decomposed).
+    Nfd,
+
+    /// A setting not known to this crate.
+    Unknown(i32),
+}
+
+impl From<i32> for InputNormalization {
+    fn from(n: i32) -> Self {
+        match n {
+            0 => InputNormalization::None,
+            1 => InputNormalization::Nfc,
+            2 => InputNormalization::Nfd,
+            n => InputNormalization::Unknown(n),
+        }
+    }
+}
+
+/// XeTeX's input settings, as saved in a format.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InputSettings {
+    /// The encoding used for files opened with `\input` and `\openin`, as set
+    /// by `\XeTeXdefaultencoding`.
+    ///
+    /// `\XeTeXinputencoding` only affects the file currently being read, so
+    /// it doesn't leave any trace in the format.
+    pub default_encoding: InputEncoding,
+
+    /// The normalization applied to input, set by `\XeTeXinputnormalization`.
+    pub normalization: InputNormalization,
+}
@@ -14,7 +14,7 @@ use crate::{
 };
 
 /// Information about the primitive associated with an integer parameter
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum IntParPrimitiveKind {
     /// No primitive.
     None,
@@ -27,7 +27,7 @@ pub enum IntParPrimitiveKind {
 }
 
 /// Information about integer parameters.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct IntPar {
     /// The name of the parameter.
     name: &'static str,
@@ -39,6 +39,47 @@ pub struct IntPar {
     since: FormatVersion,
 }
 
+impl IntPar {
+    /// Get the name of this parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the name of the primitive associated with this parameter, if any.
+    pub fn primitive_name(&self) -> Option<String> {
+        match self.primitive_kind {
+            IntParPrimitiveKind::None => None,
+            IntParPrimitiveKind::Standard => Some(self.name.replace('_', "")),
+            IntParPrimitiveKind::CustomName(s) => Some(s.replace('_', "")),
+        }
+    }
+
+    /// Get the name of the symbol giving this parameter's offset from
+    /// `INT_BASE`.
+    pub fn symbol_name(&self) -> String {
+        format!("INT_PAR__{}", self.name.to_lowercase())
+    }
+
+    /// Get the value that this parameter has in a freshly initialized engine.
+    ///
+    /// Most parameters start out as zero, but a few, like `\mag`, have other
+    /// initial values.
+    pub fn initial_value(&self) -> i32 {
+        match self.name {
+            "mag" => 1000,
+            "tolerance" => 10000,
+            "max_dead_cycles" => 25,
+            "hang_after" => 1,
+            "escape_char" => '\\' as i32,
+            "end_line_char" => 13,
+            "char_sub_def_min" => 256,
+            "char_sub_def_max" => -1,
+            "XeTeX_hyphenatable_length" => 63,
+            _ => 0,
+        }
+    }
+}
+
 const INT_PARS: &[IntPar] = &[
     IntPar {
         name: "pretolerance",
@@ -484,11 +525,7 @@ pub fn get_intpars_for_version(
     for p in INT_PARS {
         if version >= p.since {
             r.push(*p);
-            symbols.add(
-                SymbolCategory::IntPars,
-                format!("INT_PAR__{}", p.name.to_lowercase()),
-                n,
-            )?;
+            symbols.add(SymbolCategory::IntPars, p.symbol_name(), n)?;
             n += 1;
         }
     }
@@ -500,18 +537,12 @@ pub fn get_intpars_for_version(
 /// Emit initializers for intpar primitives in the C header.
 pub fn emit_c_header_primitives<W: Write>(pars: &[IntPar], mut stream: W) -> Result<()> {
     for par in pars {
-        let (has_prim, prim_name) = match par.primitive_kind {
-            IntParPrimitiveKind::None => (false, ""),
-            IntParPrimitiveKind::Standard => (true, par.name),
-            IntParPrimitiveKind::CustomName(s) => (true, s),
-        };
-
-        if has_prim {
+        if let Some(prim_name) = par.primitive_name() {
             writeln!(
                 stream,
-                "    {{ \"{}\", ASSIGN_INT, INT_BASE + INT_PAR__{}, xf_prim_init_none }}, \\",
-                prim_name.replace('_', ""),
-                par.name.to_lowercase(),
+                "    {{ \"{}\", ASSIGN_INT, INT_BASE + {}, xf_prim_init_none }}, \\",
+                prim_name,
+                par.symbol_name(),
             )?;
         }
     }
@@ -50,18 +50,28 @@ mod parseutils;
 
 pub mod base;
 pub mod catcodes;
+pub mod codes;
 pub mod commands;
 pub mod cshash;
+pub mod diff;
 pub mod dimenpars;
 pub mod engine;
 pub mod enums;
 pub mod eqtb;
 pub mod etexpenalties;
+pub mod fixed;
+pub mod fonts;
 pub mod format;
 pub mod gluepars;
+pub mod hyphenation;
+pub mod input;
 pub mod intpars;
 pub mod locals;
+pub mod mathcodes;
 pub mod mem;
+pub mod registers;
+pub mod settings;
+pub mod snapshot;
 pub mod stringtable;
 pub mod symbols;
 pub mod tokenlist;
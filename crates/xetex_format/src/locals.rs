@@ -19,7 +19,7 @@ use crate::{
 };
 
 /// Different kinds of "local" parameters.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum LocalParKind {
     /// A token list.
     TokenList,
@@ -29,7 +29,7 @@ pub enum LocalParKind {
 }
 
 /// Information about "local" parameters.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct LocalPar {
     /// The name of the parameter.
     name: &'static str,
@@ -44,6 +44,31 @@ pub struct LocalPar {
     since: FormatVersion,
 }
 
+impl LocalPar {
+    /// Get the name of this parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the kind of this parameter.
+    pub fn kind(&self) -> LocalParKind {
+        self.kind
+    }
+
+    /// Get the name of the primitive associated with this parameter.
+    pub fn primitive_name(&self) -> String {
+        self.custom_primitive_name
+            .unwrap_or(self.name)
+            .replace('_', "")
+    }
+
+    /// Get the name of the symbol giving this parameter's offset from
+    /// `LOCAL_BASE`.
+    pub fn symbol_name(&self) -> String {
+        format!("LOCAL__{}", self.name.to_lowercase())
+    }
+}
+
 const LOCAL_PARS: &[LocalPar] = &[
     LocalPar {
         name: "par_shape",
@@ -137,11 +162,7 @@ pub fn get_local_pars_for_version(
     for p in LOCAL_PARS {
         if version >= p.since {
             r.push(*p);
-            symbols.add(
-                SymbolCategory::LocalPars,
-                format!("LOCAL__{}", p.name.to_lowercase()),
-                n,
-            )?;
+            symbols.add(SymbolCategory::LocalPars, p.symbol_name(), n)?;
             n += 1;
         }
     }
@@ -158,17 +179,12 @@ pub fn emit_c_header_primitives<W: Write>(pars: &[LocalPar], mut stream: W) -> R
             LocalParKind::TokenList => "ASSIGN_TOKS",
         };
 
-        let prim_name = match par.custom_primitive_name {
-            Some(s) => s,
-            None => par.name,
-        };
-
         writeln!(
             stream,
-            "    {{ \"{}\", {}, LOCAL_BASE + LOCAL__{}, xf_prim_init_none }}, \\",
-            prim_name.replace('_', ""),
+            "    {{ \"{}\", {}, LOCAL_BASE + {}, xf_prim_init_none }}, \\",
+            par.primitive_name(),
             cmd,
-            par.name.to_lowercase(),
+            par.symbol_name(),
         )?;
     }
 
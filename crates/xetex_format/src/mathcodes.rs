@@ -0,0 +1,88 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! XeTeX's extended math codes.
+//!
+//! Classic TeX packs a math code into 15 bits. XeTeX's `\Umathcode` needs room
+//! for full Unicode slots and 256 families, so each code is stored in a full
+//! 32-bit word: the family in the top 8 bits, then the 3-bit class, then the
+//! 21-bit character slot.
+
+/// The slot value that marks a character as active in math mode, as with
+/// `\mathcode`s of `"8000`.
+pub const ACTIVE_MATH_CHAR: i32 = 0x1F_FFFF;
+
+const VAR_FAM_CLASS: u8 = 7;
+
+/// A decoded `\Umathcode`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct UMathCode {
+    /// The math class, from 0 (ordinary) to 7 ("variable family").
+    pub class: u8,
+
+    /// The math family.
+    pub family: u8,
+
+    /// The character slot within the family's font.
+    pub slot: i32,
+}
+
+impl UMathCode {
+    /// Get the math code that a character has in a freshly initialized
+    /// engine.
+    ///
+    /// Every character maps to itself in family 0, except that digits are of
+    /// the "variable family" class and Latin letters are too, in family 1.
+    pub fn default_for(c: i32) -> UMathCode {
+        let (class, family) = match char::from_u32(c as u32) {
+            Some('0'..='9') => (VAR_FAM_CLASS, 0),
+            Some('A'..='Z') | Some('a'..='z') => (VAR_FAM_CLASS, 1),
+            _ => (0, 0),
+        };
+
+        UMathCode {
+            class,
+            family,
+            slot: c,
+        }
+    }
+
+    /// Check whether this code makes its character active in math mode.
+    pub fn is_active(&self) -> bool {
+        self.slot == ACTIVE_MATH_CHAR
+    }
+}
+
+impl From<i32> for UMathCode {
+    fn from(code: i32) -> Self {
+        let code = code as u32;
+
+        UMathCode {
+            class: ((code >> 21) & 0x07) as u8,
+            family: ((code >> 24) & 0xFF) as u8,
+            slot: (code & 0x1F_FFFF) as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        // \Umathcode`a = 7 1 `a
+        let a = 0x61 + (7 << 21) + (1 << 24);
+        assert_eq!(UMathCode::from(a), UMathCode::default_for(0x61));
+
+        // \Umathcode"2211 = 1 255 "1D6F4
+        let c = UMathCode::from(0x1D6F4 + (1 << 21) + (255 << 24));
+        assert_eq!(c.class, 1);
+        assert_eq!(c.family, 255);
+        assert_eq!(c.slot, 0x1D6F4);
+        assert!(!c.is_active());
+
+        assert!(UMathCode::from(ACTIVE_MATH_CHAR).is_active());
+        assert_eq!(UMathCode::default_for(0x2211).class, 0);
+    }
+}
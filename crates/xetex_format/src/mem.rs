@@ -3,33 +3,94 @@
 
 //! The TeX dynamic memory array.
 
-use nom::{
-    multi::count,
-    number::complete::{be_i32, be_u8},
-    IResult,
-};
+use nom::{bytes::complete::take, multi::count, number::complete::be_i32};
+use tectonic_errors::prelude::*;
 
 use crate::{
-    base::{self, MIN_HALFWORD, SIZEOF_MEMORY_WORD},
+    base::{self, MAX_HALFWORD, MIN_HALFWORD, SIZEOF_MEMORY_WORD, TEX_NULL},
     engine::Engine,
-    parseutils,
+    fixed::Fixed,
+    parseutils::{self, ParseResult},
 };
 
 pub type MemPointer = i32;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Memory {
     pub mem: Vec<u8>,
 
     /// This is needed by the format-file parser for a bounds check.
     pub lo_mem_max: MemPointer,
+
+    /// The head of the list of free variable-size nodes, as used by the
+    /// engine's allocator.
+    pub rover: MemPointer,
+
+    /// The roots of the e-TeX sparse arrays, indexed by type (`INT_VAL`
+    /// through `INTER_CHAR_VAL`).
+    pub sa_roots: Vec<MemPointer>,
+}
+
+/// A decoded glue specification.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct GlueSpec {
+    /// The natural width of the glue.
+    pub width: Fixed,
+
+    /// The stretch component of the glue. For infinite orders, this counts
+    /// units of `fil`, `fill`, or `filll` rather than points.
+    pub stretch: Fixed,
+
+    /// The shrink component of the glue.
+    pub shrink: Fixed,
+
+    /// The order of infinity of the stretch component (0 = finite, 1 = fil,
+    /// etc.).
+    pub stretch_order: i16,
+
+    /// The order of infinity of the shrink component.
+    pub shrink_order: i16,
+}
+
+/// The state of the lists that TeX's "last item" commands inspect.
+///
+/// `\lastbox`, `\lastkern`, `\lastskip`, and `\lastpenalty` look at the tail
+/// of the current list, which at the outermost level is the list of recent
+/// contributions to the page. The engine will only `\dump` when that list and
+/// the current page are empty, so in a well-formed format every field here is
+/// true. The `last_glue`-style globals that record the most recently
+/// contributed items aren't saved in formats at all.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LastNodeState {
+    /// Whether the contribution list, linked from `CONTRIB_HEAD`, is empty.
+    pub contributions_empty: bool,
+
+    /// Whether the current page, linked from `PAGE_HEAD`, is empty.
+    pub page_empty: bool,
+
+    /// Whether the ring of insertions on the current page, linked from
+    /// `PAGE_INS_HEAD`, is empty.
+    pub insertions_empty: bool,
+}
+
+impl LastNodeState {
+    /// Returns true if none of the lists hold any nodes.
+    pub fn is_clean(&self) -> bool {
+        self.contributions_empty && self.page_empty && self.insertions_empty
+    }
 }
 
 const HI_MEM_STAT_USAGE: i32 = 15;
+const DIMEN_VAL_LIMIT: i32 = 0x80;
 const N_SERIALIZED_SA_ROOTS: usize = 7; // INT_VAL => INTER_CHAR_VAL, inclusive
 
 impl Memory {
-    pub(crate) fn parse<'a>(input: &'a [u8], engine: &Engine) -> IResult<&'a [u8], Self> {
+    /// Parse the `mem` array, storing it in *buf*.
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        engine: &Engine,
+        buf: Vec<u8>,
+    ) -> ParseResult<'a, Self> {
         let mem_top = engine.symbols.lookup("MEM_TOP") as usize;
 
         // lower limit hardcoded (?)
@@ -39,14 +100,14 @@ impl Memory {
         // lower limit hardcoded
         let (input, rover) = parseutils::ranged_be_i32(20, lo_mem_max)(input)?;
 
-        let (input, _sa_roots) = count(
+        let (input, sa_roots) = count(
             parseutils::ranged_be_i32(MIN_HALFWORD, lo_mem_max),
             N_SERIALIZED_SA_ROOTS,
         )(input)?;
 
         // Compressed memory loading;
 
-        let mut mem = vec![0; (mem_top as usize + 1) * SIZEOF_MEMORY_WORD];
+        let mut mem = parseutils::zeroed_buffer(buf, (mem_top as usize + 1) * SIZEOF_MEMORY_WORD);
         let mut input = input;
         let mut p = 0;
         let mut q = rover;
@@ -55,16 +116,20 @@ impl Memory {
             let nb = (q + 2 - p) as usize * SIZEOF_MEMORY_WORD;
 
             // TODO: read straight into mem?
-            let (new_input, block) = count(be_u8, nb)(input)?;
-            input = new_input;
+            let (new_input, block) = take(nb)(input)?;
             let idx = p as usize * SIZEOF_MEMORY_WORD;
-            mem[idx..idx + nb].copy_from_slice(&block[..]);
+            mem[idx..idx + nb].copy_from_slice(block);
 
             let ofs = base::memword_read_b32_s0(&mem[..], q);
-            p = q + ofs;
-            assert!(p <= lo_mem_max);
+            p = parseutils::check_range(input, q.saturating_add(ofs), q + 1, lo_mem_max)?;
 
-            q = base::memword_read_b32_s1(&mem[..], q + 1);
+            let next_q = base::memword_read_b32_s1(&mem[..], q + 1);
+            if next_q != rover {
+                parseutils::check_range(input, next_q, p, lo_mem_max)?;
+            }
+
+            q = next_q;
+            input = new_input;
 
             if q == rover {
                 break;
@@ -73,9 +138,9 @@ impl Memory {
 
         // Loading the rest of low memory. TODO: straight into `mem`?
         let nb = (lo_mem_max + 1 - p as i32) as usize * SIZEOF_MEMORY_WORD;
-        let (input, block) = count(be_u8, nb)(input)?;
+        let (input, block) = take(nb)(input)?;
         let idx = p as usize * SIZEOF_MEMORY_WORD;
-        mem[idx..idx + nb].copy_from_slice(&block[..]);
+        mem[idx..idx + nb].copy_from_slice(block);
 
         let (input, hi_mem_min) =
             parseutils::ranged_be_i32(lo_mem_max + 1, mem_top as i32 - HI_MEM_STAT_USAGE)(input)?;
@@ -83,14 +148,149 @@ impl Memory {
         let (input, _avail) = parseutils::ranged_be_i32(MIN_HALFWORD, mem_top as i32)(input)?;
 
         let nb = (mem_top + 1 - hi_mem_min as usize) * SIZEOF_MEMORY_WORD;
-        let (input, block) = count(be_u8, nb)(input)?;
+        let (input, block) = take(nb)(input)?;
         mem[hi_mem_min as usize * SIZEOF_MEMORY_WORD
             ..hi_mem_min as usize * SIZEOF_MEMORY_WORD + nb]
-            .copy_from_slice(&block[..]);
+            .copy_from_slice(block);
 
         let (input, _var_used) = be_i32(input)?;
         let (input, _dyn_used) = be_i32(input)?;
-        Ok((input, Memory { mem, lo_mem_max }))
+        Ok((
+            input,
+            Memory {
+                mem,
+                lo_mem_max,
+                rover,
+                sa_roots,
+            },
+        ))
+    }
+
+    /// Skip over the `mem` array without decoding it, returning `lo_mem_max`.
+    ///
+    /// The lengths of the low-memory blocks are only discoverable by following
+    /// the chain of free blocks, so a few words of each block still need to be
+    /// read.
+    pub(crate) fn skip<'a>(input: &'a [u8], engine: &Engine) -> ParseResult<'a, MemPointer> {
+        let mem_top = engine.symbols.lookup("MEM_TOP") as i32;
+
+        let (input, lo_mem_max) =
+            parseutils::ranged_be_i32(1019, mem_top - HI_MEM_STAT_USAGE)(input)?;
+        let (input, rover) = parseutils::ranged_be_i32(20, lo_mem_max)(input)?;
+        let (mut input, _sa_roots) = take(N_SERIALIZED_SA_ROOTS * 4)(input)?;
+
+        let mut p = 0;
+        let mut q = rover;
+
+        loop {
+            let nb = (q + 2 - p) as usize * SIZEOF_MEMORY_WORD;
+            let (new_input, block) = take(nb)(input)?;
+
+            let ofs = base::memword_read_b32_s0(block, q - p);
+            let next_q = base::memword_read_b32_s1(block, q + 1 - p);
+            p = parseutils::check_range(input, q.saturating_add(ofs), q + 1, lo_mem_max)?;
+
+            if next_q != rover {
+                parseutils::check_range(input, next_q, p, lo_mem_max)?;
+            }
+
+            q = next_q;
+            input = new_input;
+
+            if q == rover {
+                break;
+            }
+        }
+
+        let (input, _) = take((lo_mem_max + 1 - p) as usize * SIZEOF_MEMORY_WORD)(input)?;
+        let (input, hi_mem_min) =
+            parseutils::ranged_be_i32(lo_mem_max + 1, mem_top - HI_MEM_STAT_USAGE)(input)?;
+        let (input, _avail) = be_i32(input)?;
+        let (input, _) = take((mem_top + 1 - hi_mem_min) as usize * SIZEOF_MEMORY_WORD)(input)?;
+        let (input, _var_used) = be_i32(input)?;
+        let (input, _dyn_used) = be_i32(input)?;
+        Ok((input, lo_mem_max))
+    }
+
+    /// Check the integrity of the list of free variable-size nodes.
+    ///
+    /// This walks the doubly-linked ring of free nodes, starting from
+    /// `rover` just as the engine's allocator does, and checks that each node
+    /// is marked as free, lies within low memory, and is properly linked to
+    /// its neighbors. An error describing the first problem found is
+    /// returned.
+    pub fn validate(&self) -> Result<()> {
+        let read_s0 = |p| base::memword_read_b32_s0(&self.mem[..], p);
+        let read_s1 = |p| base::memword_read_b32_s1(&self.mem[..], p);
+        let in_low_mem = |p| (0..self.lo_mem_max).contains(&p);
+
+        ensure!(
+            in_low_mem(self.rover),
+            "rover {} outside of low memory",
+            self.rover
+        );
+
+        let mut q = self.rover;
+
+        // Each node takes at least two words, which bounds the number of
+        // steps if the links are corrupt.
+        for _ in 0..self.lo_mem_max / 2 + 1 {
+            ensure!(
+                read_s1(q) == MAX_HALFWORD,
+                "free node {} is not marked as empty",
+                q
+            );
+
+            let size = read_s0(q);
+            ensure!(
+                size >= 2 && q.saturating_add(size) <= self.lo_mem_max,
+                "free node {} has bad size {}",
+                q,
+                size
+            );
+
+            let next = read_s1(q + 1);
+            ensure!(
+                in_low_mem(next),
+                "free node {} links to {}, outside of low memory",
+                q,
+                next
+            );
+            ensure!(
+                read_s0(next + 1) == q,
+                "free node {} is not linked back from its successor {}",
+                q,
+                next
+            );
+
+            q = next;
+
+            if q == self.rover {
+                return Ok(());
+            }
+        }
+
+        bail!(
+            "the list of free nodes starting at {} never ends",
+            self.rover
+        )
+    }
+
+    /// Report whether the lists examined by the "last item" commands are
+    /// empty.
+    ///
+    /// These lists live at fixed locations at the very top of `mem`.
+    pub fn last_node_state(&self) -> LastNodeState {
+        let page_ins_head = (self.mem.len() / SIZEOF_MEMORY_WORD) as MemPointer - 1;
+        let contrib_head = page_ins_head - 1;
+        let page_head = page_ins_head - 2;
+        let link = |p| base::memword_read_b32_s1(&self.mem[..], p);
+
+        LastNodeState {
+            contributions_empty: link(contrib_head) == TEX_NULL,
+            page_empty: link(page_head) == TEX_NULL,
+            insertions_empty: link(page_ins_head) == page_ins_head,
+        }
     }
 
     pub fn decode_toklist(&self, index: MemPointer) -> (i32, MemPointer) {
@@ -98,4 +298,237 @@ impl Memory {
         let next = base::memword_read_b32_s1(&self.mem[..], index);
         (value, next)
     }
+
+    /// Decode the glue specification at *index*.
+    ///
+    /// If the specification doesn't lie within `mem`, as can happen with a
+    /// corrupt format, zero glue is returned.
+    pub fn decode_glue(&self, index: MemPointer) -> GlueSpec {
+        if !self.contains_words(index, 4) {
+            return GlueSpec::default();
+        }
+
+        GlueSpec {
+            width: Fixed(base::memword_read_b32_s1(&self.mem[..], index + 1)),
+            stretch: Fixed(base::memword_read_b32_s1(&self.mem[..], index + 2)),
+            shrink: Fixed(base::memword_read_b32_s1(&self.mem[..], index + 3)),
+            stretch_order: base::memword_read_b16_s1(&self.mem[..], index),
+            shrink_order: base::memword_read_b16_s0(&self.mem[..], index),
+        }
+    }
+
+    /// Decode the `\parshape` specification at *index*, as the indentation
+    /// and length of each line.
+    pub fn decode_par_shape(&self, index: MemPointer) -> Vec<(Fixed, Fixed)> {
+        let n = base::memword_read_b32_s0(&self.mem[..], index);
+        let n_words = (self.mem.len() / SIZEOF_MEMORY_WORD) as i64;
+
+        // Don't trust a corrupt count to stay within `mem`.
+        if n < 0 || index as i64 + 2 * n as i64 >= n_words {
+            return Vec::new();
+        }
+
+        (1..=n)
+            .map(|j| {
+                (
+                    Fixed(base::memword_read_b32_s1(&self.mem[..], index + 2 * j - 1)),
+                    Fixed(base::memword_read_b32_s1(&self.mem[..], index + 2 * j)),
+                )
+            })
+            .collect()
+    }
+
+    /// Find element *n* of the e-TeX sparse array of type *t*, if it exists.
+    ///
+    /// This mirrors `find_sa_element()` in the engine, without the option to
+    /// create the element if it's missing. Pointers that lead outside of
+    /// `mem`, as in a corrupt format, are treated as null, so the element
+    /// returned can always be passed to [`Memory::sa_int`] or
+    /// [`Memory::sa_ptr`], as appropriate for *t*.
+    pub fn find_sa_element(&self, t: usize, n: i32) -> Option<MemPointer> {
+        let mut p = *self.sa_roots.get(t)?;
+
+        for i in [n / 0x40000, (n / 4096) % 64, (n / 64) % 64, n % 64].iter() {
+            if p == TEX_NULL {
+                return None;
+            }
+
+            let q = p + i / 2 + 1;
+
+            if !self.contains_words(q, 1) {
+                return None;
+            }
+
+            p = if i % 2 == 1 {
+                base::memword_read_b32_s1(&self.mem[..], q)
+            } else {
+                base::memword_read_b32_s0(&self.mem[..], q)
+            };
+        }
+
+        // Integer and dimension elements have their values in a third word.
+        let size = if (t as i32) * 64 < DIMEN_VAL_LIMIT {
+            3
+        } else {
+            2
+        };
+
+        if p == TEX_NULL || !self.contains_words(p, size) {
+            None
+        } else {
+            Some(p)
+        }
+    }
+
+    /// Get the register number of the sparse-array element *q*.
+    ///
+    /// This mirrors `print_sa_num()` in the engine. Elements holding integers
+    /// and dimensions record their numbers directly; for the others, the
+    /// number has to be reassembled from the indices of the element and its
+    /// ancestors in the tree. Returns None if any of the pointers involved
+    /// lead outside of `mem`.
+    pub fn sa_num(&self, q: MemPointer) -> Option<i32> {
+        let index = |p| {
+            if self.contains_words(p, 1) {
+                Some(base::memword_read_b16_s1(&self.mem[..], p) as i32)
+            } else {
+                None
+            }
+        };
+        let link = |p| {
+            if self.contains_words(p, 1) {
+                Some(base::memword_read_b32_s1(&self.mem[..], p))
+            } else {
+                None
+            }
+        };
+
+        if index(q)? < DIMEN_VAL_LIMIT {
+            return link(q + 1);
+        }
+
+        let mut n = index(q)? % 64;
+        let q = link(q)?;
+        n += 64 * index(q)?;
+        let q = link(q)?;
+        Some(n + 64 * 64 * (index(q)? + 64 * index(link(q)?)?))
+    }
+
+    /// Check whether the *n* words starting at *p* all lie within `mem`.
+    fn contains_words(&self, p: MemPointer, n: i32) -> bool {
+        p >= 0 && (p as i64 + n as i64) as usize <= self.mem.len() / SIZEOF_MEMORY_WORD
+    }
+
+    /// Get the integer value of the sparse-array element *p*, for arrays of type
+    /// `INT_VAL` or `DIMEN_VAL`.
+    pub fn sa_int(&self, p: MemPointer) -> i32 {
+        base::memword_read_b32_s1(&self.mem[..], p + 2)
+    }
+
+    /// Get the pointer value of the sparse-array element *p*, for arrays of type
+    /// `GLUE_VAL` and above.
+    pub fn sa_ptr(&self, p: MemPointer) -> MemPointer {
+        base::memword_read_b32_s1(&self.mem[..], p + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an all-zero `mem` of 100 words for testing.
+    fn test_memory(lo_mem_max: MemPointer, sa_roots: Vec<MemPointer>) -> Memory {
+        Memory {
+            mem: vec![0; 100 * SIZEOF_MEMORY_WORD],
+            lo_mem_max,
+            rover: 20,
+            sa_roots,
+        }
+    }
+
+    #[test]
+    fn validate_free_list() {
+        let mut memory = test_memory(99, Vec::new());
+
+        // Two free nodes, at 20 and 40, linked to each other.
+        for (q, size, other) in [(20, 10, 40), (40, 5, 20)].iter() {
+            base::memword_write_b32_s1(&mut memory.mem[..], *q, MAX_HALFWORD);
+            base::memword_write_b32_s0(&mut memory.mem[..], *q, *size);
+            base::memword_write_b32_s1(&mut memory.mem[..], q + 1, *other);
+            base::memword_write_b32_s0(&mut memory.mem[..], q + 1, *other);
+        }
+
+        memory.validate().unwrap();
+
+        base::memword_write_b32_s0(&mut memory.mem[..], 41, 30);
+        assert!(memory.validate().is_err());
+    }
+
+    #[test]
+    fn sa_num() {
+        let mut memory = test_memory(99, Vec::new());
+
+        // A \count element records its number directly.
+        base::memword_write_b16_s1(&mut memory.mem[..], 30, 5);
+        base::memword_write_b32_s1(&mut memory.mem[..], 31, 300);
+        assert_eq!(memory.sa_num(30), Some(300));
+
+        // A \skip element, linked to its ancestors, whose number is built
+        // from the indices at each level.
+        let chain = [(40, 2 * 64 + 0x05), (50, 0x0D), (60, 0x08), (70, 0x01)];
+
+        for (i, (q, index)) in chain.iter().enumerate() {
+            base::memword_write_b16_s1(&mut memory.mem[..], *q, *index);
+
+            if let Some((parent, _)) = chain.get(i + 1) {
+                base::memword_write_b32_s1(&mut memory.mem[..], *q, *parent);
+            }
+        }
+
+        assert_eq!(memory.sa_num(40), Some(0x40000 + 8 * 4096 + 13 * 64 + 5));
+
+        // Links out of `mem`, as in a corrupt format, are caught.
+        base::memword_write_b32_s1(&mut memory.mem[..], 60, 1000);
+        assert_eq!(memory.sa_num(40), None);
+        assert_eq!(memory.sa_num(-1), None);
+        assert_eq!(memory.sa_num(100), None);
+    }
+
+    #[test]
+    fn find_sa_element_bounds() {
+        let memory = test_memory(99, vec![TEX_NULL, 5000, 99]);
+
+        assert_eq!(memory.find_sa_element(0, 300), None);
+        assert_eq!(memory.find_sa_element(1, 300), None);
+        assert_eq!(memory.find_sa_element(2, 300), None);
+        assert_eq!(memory.find_sa_element(3, 300), None);
+    }
+
+    #[test]
+    fn decode_glue_bounds() {
+        let mut memory = test_memory(99, Vec::new());
+
+        base::memword_write_b32_s1(&mut memory.mem[..], 97, 65536);
+        assert_eq!(memory.decode_glue(96).width, Fixed(65536));
+
+        for p in &[-1, 97, 100, TEX_NULL, i32::MAX] {
+            assert_eq!(memory.decode_glue(*p), GlueSpec::default());
+        }
+    }
+
+    #[test]
+    fn last_node_state() {
+        let mut memory = test_memory(50, Vec::new());
+
+        base::memword_write_b32_s1(&mut memory.mem[..], 99, 99);
+        base::memword_write_b32_s1(&mut memory.mem[..], 98, TEX_NULL);
+        base::memword_write_b32_s1(&mut memory.mem[..], 97, TEX_NULL);
+        assert!(memory.last_node_state().is_clean());
+
+        base::memword_write_b32_s1(&mut memory.mem[..], 98, 60);
+        let state = memory.last_node_state();
+        assert!(!state.contributions_empty);
+        assert!(state.page_empty);
+        assert!(!state.is_clean());
+    }
 }
@@ -4,44 +4,157 @@
 //! Format-file parsing utilities.
 
 use nom::{
-    error::ErrorKind as NomErrorKind, error::ParseError, number::complete::be_i32, Err as NomErr,
-    IResult, InputIter, InputLength, Slice,
+    error::ErrorKind as NomErrorKind, error::ParseError, multi::count, number::complete::be_i32,
+    Err as NomErr, IResult, InputIter, InputLength, Parser, Slice,
 };
-use std::ops::RangeFrom;
+use std::{fmt, ops::RangeFrom};
 
-pub fn satisfy_be_i32<I, Error: ParseError<I>>(
-    expected: i32,
-) -> impl Fn(I) -> IResult<I, i32, Error>
+/// The result type of the format-file parsers.
+pub type ParseResult<'a, T> = IResult<&'a [u8], T, Error<&'a [u8]>>;
+
+/// A parse error, possibly annotated with the details of a failed range check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error<I> {
+    /// The input at the point where the error occurred.
+    pub input: I,
+
+    /// The kind of error.
+    pub kind: NomErrorKind,
+
+    /// If the error was a failed range check, its details.
+    pub range: Option<RangeViolation>,
+}
+
+/// Details about a value that fell outside of its expected range.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RangeViolation {
+    /// The value that was actually read.
+    pub value: i32,
+
+    /// The minimum acceptable value, inclusive.
+    pub min: i32,
+
+    /// The maximum acceptable value, inclusive.
+    pub max: i32,
+}
+
+impl fmt::Display for RangeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "value {} is not the expected {}", self.value, self.min)
+        } else {
+            write!(
+                f,
+                "value {} outside expected range [{}, {}]",
+                self.value, self.min, self.max
+            )
+        }
+    }
+}
+
+impl<I> ParseError<I> for Error<I> {
+    fn from_error_kind(input: I, kind: NomErrorKind) -> Self {
+        Error {
+            input,
+            kind,
+            range: None,
+        }
+    }
+
+    fn append(_input: I, _kind: NomErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I> Error<I> {
+    fn out_of_range(input: I, value: i32, min: i32, max: i32) -> Self {
+        Error {
+            input,
+            kind: NomErrorKind::Satisfy,
+            range: Some(RangeViolation { value, min, max }),
+        }
+    }
+}
+
+impl Error<&[u8]> {
+    /// Describe this error, reporting its location as an offset into *full*,
+    /// which should be the complete input that was being parsed.
+    pub fn describe(&self, full: &[u8]) -> String {
+        let offset = full.len().saturating_sub(self.input.len());
+
+        if let Some(r) = self.range {
+            format!("{} at offset 0x{:x}", r, offset)
+        } else {
+            format!("{} at offset 0x{:x}", self.kind.description(), offset)
+        }
+    }
+}
+
+/// Check that *value* lies between *min* and *max*, inclusive, reporting a
+/// range violation at *input* if not.
+pub(crate) fn check_range<I>(
+    input: I,
+    value: i32,
+    min: i32,
+    max: i32,
+) -> Result<i32, NomErr<Error<I>>> {
+    if value < min || value > max {
+        Err(NomErr::Error(Error::out_of_range(input, value, min, max)))
+    } else {
+        Ok(value)
+    }
+}
+
+pub fn satisfy_be_i32<I>(expected: i32) -> impl Fn(I) -> IResult<I, i32, Error<I>>
 where
-    I: Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
+    I: Clone + Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
 {
     move |i: I| {
-        let (new_input, value) = be_i32(i)?;
+        let (new_input, value) = be_i32(i.clone())?;
         if value != expected {
-            return Err(NomErr::Error(Error::from_error_kind(
-                new_input,
-                NomErrorKind::Satisfy,
+            return Err(NomErr::Error(Error::out_of_range(
+                i, value, expected, expected,
             )));
         }
         Ok((new_input, value))
     }
 }
 
-pub fn ranged_be_i32<I, Error: ParseError<I>>(
-    min: i32,
-    max: i32,
-) -> impl Fn(I) -> IResult<I, i32, Error>
+pub fn ranged_be_i32<I>(min: i32, max: i32) -> impl Fn(I) -> IResult<I, i32, Error<I>>
 where
-    I: Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
+    I: Clone + Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
 {
     move |i: I| {
-        let (new_input, value) = be_i32(i)?;
-        if value < min || value > max {
-            return Err(NomErr::Error(Error::from_error_kind(
-                new_input,
-                NomErrorKind::Satisfy,
-            )));
-        }
+        let (new_input, value) = be_i32(i.clone())?;
+        check_range(i, value, min, max)?;
         Ok((new_input, value))
     }
 }
+
+/// Prepare *buf* to hold *len* zero bytes, reusing its allocation if possible.
+pub(crate) fn zeroed_buffer(mut buf: Vec<u8>, len: usize) -> Vec<u8> {
+    buf.clear();
+    buf.resize(len, 0);
+    buf
+}
+
+/// Like `nom::multi::count`, but first checks that the input is long enough to
+/// contain *n* items of *size* bytes each.
+///
+/// `count` preallocates its output, so without this check a corrupt item count
+/// can trigger an enormous allocation.
+pub fn sized_count<'a, O, F>(
+    f: F,
+    n: usize,
+    size: usize,
+) -> impl FnMut(&'a [u8]) -> ParseResult<'a, Vec<O>>
+where
+    F: Parser<&'a [u8], O, Error<&'a [u8]>>,
+{
+    let mut inner = count(f, n);
+
+    move |i: &'a [u8]| match n.checked_mul(size) {
+        Some(nb) if nb <= i.len() => inner(i),
+        _ => Err(NomErr::Error(Error::from_error_kind(i, NomErrorKind::Eof))),
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! References to TeX registers by their TeX names, like `\count42`.
+
+use std::{fmt, ops::Range, str::FromStr};
+use tectonic_errors::prelude::*;
+
+use crate::base::MAX_REG_NUM;
+
+/// The kinds of TeX registers.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RegisterKind {
+    /// A `\count` register.
+    Count,
+
+    /// A `\dimen` register.
+    Dimen,
+
+    /// A `\skip` register.
+    Skip,
+
+    /// A `\muskip` register.
+    MuSkip,
+
+    /// A `\toks` register.
+    Toks,
+
+    /// A `\box` register.
+    Box,
+}
+
+impl RegisterKind {
+    /// Get the name of the TeX primitive that accesses this kind of
+    /// register, without a leading backslash.
+    pub fn primitive_name(self) -> &'static str {
+        match self {
+            RegisterKind::Count => "count",
+            RegisterKind::Dimen => "dimen",
+            RegisterKind::Skip => "skip",
+            RegisterKind::MuSkip => "muskip",
+            RegisterKind::Toks => "toks",
+            RegisterKind::Box => "box",
+        }
+    }
+
+    const ALL: [RegisterKind; 6] = [
+        RegisterKind::Count,
+        RegisterKind::Dimen,
+        RegisterKind::Skip,
+        RegisterKind::MuSkip,
+        RegisterKind::Toks,
+        RegisterKind::Box,
+    ];
+}
+
+/// A reference to a single register, like `\count42`.
+///
+/// This can be parsed from, and formats as, the TeX-style names used in this
+/// crate's dumps. The leading backslash is optional when parsing.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RegisterRef {
+    /// The kind of register.
+    pub kind: RegisterKind,
+
+    /// The register number, no larger than [`MAX_REG_NUM`].
+    pub number: usize,
+}
+
+impl RegisterRef {
+    /// Get the range of register numbers containing just this register.
+    ///
+    /// This is convenient for passing to methods like
+    /// [`crate::format::Format::count_registers_in`].
+    pub fn range(&self) -> Range<usize> {
+        self.number..self.number + 1
+    }
+}
+
+impl FromStr for RegisterRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let text = s.strip_prefix('\\').unwrap_or(s);
+
+        for kind in RegisterKind::ALL.iter().copied() {
+            let digits = match text.strip_prefix(kind.primitive_name()) {
+                Some(d) => d,
+                None => continue,
+            };
+
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+
+            let number = digits.parse::<usize>().unwrap_or(usize::MAX);
+            ensure!(
+                number <= MAX_REG_NUM,
+                "register number in `{}` must be at most {}",
+                s,
+                MAX_REG_NUM
+            );
+            return Ok(RegisterRef { kind, number });
+        }
+
+        bail!("`{}` is not a register reference like `\\count42`", s);
+    }
+}
+
+/// Formats the reference as TeX would name it, e.g. `\count42`.
+impl fmt::Display for RegisterRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\\{}{}", self.kind.primitive_name(), self.number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let r: RegisterRef = "\\count42".parse().unwrap();
+        assert_eq!(r.kind, RegisterKind::Count);
+        assert_eq!(r.number, 42);
+        assert_eq!(r.range(), 42..43);
+
+        assert_eq!(
+            "muskip0".parse::<RegisterRef>().unwrap().kind,
+            RegisterKind::MuSkip
+        );
+
+        for kind in RegisterKind::ALL.iter().copied() {
+            let r = RegisterRef { kind, number: 300 };
+            assert_eq!(r.to_string().parse::<RegisterRef>().unwrap(), r);
+        }
+
+        for bad in &[
+            "\\count",
+            "\\count-1",
+            "\\count4a",
+            "\\relax",
+            "\\dimen32768",
+        ] {
+            assert!(bad.parse::<RegisterRef>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Groups of related parameter settings, as decoded from a format.
+//!
+//! Each of these structures is returned by a method on
+//! [`crate::format::Format`] that collects its values from the table of
+//! equivalents.
+
+use std::fmt;
+
+use crate::{catcodes::CatCode, fixed::Fixed, mem::GlueSpec, tokenlist::Token};
+
+/// The settings that control how much detail TeX reports in its diagnostics.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DiagnosticsSettings {
+    /// `\showboxbreadth`: the maximum number of items shown per list level
+    /// when displaying boxes.
+    pub show_box_breadth: i32,
+
+    /// `\showboxdepth`: the maximum nesting depth shown when displaying boxes.
+    pub show_box_depth: i32,
+
+    /// `\errorcontextlines`: the number of lines of context shown in error
+    /// messages.
+    pub error_context_lines: i32,
+
+    /// `\hbadness`: the badness above which underfull or overfull hboxes are
+    /// reported.
+    pub hbadness: i32,
+
+    /// `\vbadness`: the badness above which underfull or overfull vboxes are
+    /// reported.
+    pub vbadness: i32,
+}
+
+/// The settings that control the shape of paragraphs.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ParagraphShaping {
+    /// `\parshape`: the indentation and length of each line, if a shape is
+    /// set. This takes precedence over the hanging indentation.
+    pub par_shape: Vec<(Fixed, Fixed)>,
+
+    /// `\hangindent`: the amount of hanging indentation.
+    pub hang_indent: Fixed,
+
+    /// `\hangafter`: the line number after which hanging indentation starts,
+    /// or before which it applies if negative.
+    pub hang_after: i32,
+}
+
+/// XeTeX's settings for breaking lines in scripts without interword spaces.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LinebreakSettings {
+    /// The locale set by `\XeTeXlinebreaklocale`, if any. Where this is
+    /// unset, XeTeX only breaks lines at spaces.
+    pub locale: Option<String>,
+
+    /// `\XeTeXlinebreakskip`: the glue inserted at the break points found
+    /// using the locale.
+    pub skip: GlueSpec,
+
+    /// `\XeTeXlinebreakpenalty`: the penalty associated with those break
+    /// points.
+    pub penalty: i32,
+}
+
+/// XeTeX's integer parameters that affect line breaking and hyphenation.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct XetexBreakSettings {
+    /// `\XeTeXdashbreakstate`: if positive, line breaks are allowed after
+    /// em and en dashes in native-font text.
+    pub dash_break_state: i32,
+
+    /// `\XeTeXhyphenatablelength`: the longest word that will be considered
+    /// for hyphenation.
+    pub hyphenatable_length: i32,
+
+    /// `\XeTeXtracingfonts`: if positive, the fonts chosen to realize
+    /// native-font requests are logged.
+    pub tracing_fonts: i32,
+}
+
+/// The settings that control how line endings are read and written.
+///
+/// TeX appends the `\endlinechar` to each line of input, so its category
+/// code determines how line ends are tokenized. The `\newlinechar` instead
+/// controls output: printing it starts a new line in the terminal or log.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LineEndingAnalysis {
+    /// `\endlinechar`: the character appended to each input line, or a value
+    /// outside of the character range if none is appended.
+    pub end_line_char: i32,
+
+    /// The category code of the `\endlinechar`, if it's a valid character.
+    pub end_line_catcode: Option<CatCode>,
+
+    /// `\newlinechar`: the character that starts a new line when printed, or
+    /// a value outside of the character range if there's none.
+    pub new_line_char: i32,
+
+    /// The category code of the `\newlinechar`, if it's a valid character.
+    pub new_line_catcode: Option<CatCode>,
+}
+
+impl LineEndingAnalysis {
+    /// Check whether line endings are handled as plain TeX sets them up.
+    ///
+    /// That is, the `\endlinechar` is a carriage return with its usual
+    /// category code, and the `\newlinechar` is disabled. Any other
+    /// combination is a customization, which may or may not be deliberate.
+    pub fn is_standard(&self) -> bool {
+        self.end_line_char == 13
+            && self.end_line_catcode == Some(CatCode::CarriageReturn)
+            && self.new_line_catcode.is_none()
+    }
+}
+
+/// The settings that control the direction of typesetting.
+///
+/// XeTeX has no equivalent of the `\bodydir`-style parameters of Omega and
+/// LuaTeX; these are all of the direction controls it provides.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DirectionSettings {
+    /// `\XeTeXupwardsmode`: if positive, lines are stacked upwards, as for
+    /// some vertical scripts, rather than downwards.
+    pub upwards_mode: i32,
+
+    /// `\TeXXeTstate`: if positive, the TeX--XeT extensions for
+    /// right-to-left typesetting (`\beginR` and friends) are enabled.
+    pub texxet_state: i32,
+
+    /// `\predisplaydirection`: the direction of the paragraph preceding a
+    /// display, which is set by TeX at the start of each display.
+    pub pre_display_direction: i32,
+}
+
+/// The settings that control the spacing around and within inline math.
+///
+/// The muskips are given in math units, 18 of which make up an em of the
+/// math symbol font, rather than in points, although they're stored in the
+/// same way as other glue.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MathSpacing {
+    /// `\mathsurround`: the space added before and after inline math.
+    pub math_surround: Fixed,
+
+    /// `\thinmuskip`: the space used, for instance, around operators.
+    pub thin_mu_skip: GlueSpec,
+
+    /// `\medmuskip`: the space used around binary operators.
+    pub med_mu_skip: GlueSpec,
+
+    /// `\thickmuskip`: the space used around relations.
+    pub thick_mu_skip: GlueSpec,
+}
+
+/// The settings that control the sizes of math delimiters.
+///
+/// A delimiter built with `\left` and `\right` must be at least
+/// `\delimiterfactor`/1000 times the size of the enclosed formula, and must
+/// also come within `\delimitershortfall` of covering it.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DelimiterSettings {
+    /// `\delimiterfactor`: the minimum size of delimiters, relative to the
+    /// formula, in thousandths.
+    pub delimiter_factor: i32,
+
+    /// `\delimitershortfall`: the largest amount by which delimiters may
+    /// fall short of the formula.
+    pub delimiter_shortfall: Fixed,
+
+    /// `\nulldelimiterspace`: the width of the null delimiter, `.`.
+    pub null_delimiter_space: Fixed,
+}
+
+/// The settings that control when overfull boxes are reported, and how they
+/// are marked.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BoxToleranceSettings {
+    /// `\hfuzz`: the amount by which an `\hbox` may be overfull before it is
+    /// reported.
+    pub hfuzz: Fixed,
+
+    /// `\vfuzz`: the amount by which a `\vbox` may be overfull before it is
+    /// reported.
+    pub vfuzz: Fixed,
+
+    /// `\overfullrule`: the width of the rule that marks overfull boxes.
+    pub overfull_rule: Fixed,
+}
+
+/// The glue parameters that govern how paragraphs are filled and justified.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ParagraphGlueSettings {
+    /// `\parskip`: the glue inserted between paragraphs.
+    pub par_skip: GlueSpec,
+
+    /// `\leftskip`: the glue at the left end of each line.
+    pub left_skip: GlueSpec,
+
+    /// `\rightskip`: the glue at the right end of each line. Ragged-right
+    /// setups give this some stretch.
+    pub right_skip: GlueSpec,
+
+    /// `\spaceskip`: the interword glue, if nonzero, overriding the font's.
+    pub space_skip: GlueSpec,
+
+    /// `\parfillskip`: the glue appended to finish the last line of a
+    /// paragraph.
+    pub par_fill_skip: GlueSpec,
+}
+
+/// The parameters that together govern the spacing between lines.
+///
+/// TeX tries to put `\baselineskip` between the baselines of consecutive
+/// boxes; if that would leave less than `\lineskiplimit` between the bottom
+/// of one and the top of the next, it uses `\lineskip` between them instead.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BaselineSettings {
+    /// `\baselineskip`: the desired distance between baselines.
+    pub baseline_skip: GlueSpec,
+
+    /// `\lineskip`: the glue used when lines would otherwise come too close.
+    pub line_skip: GlueSpec,
+
+    /// `\lineskiplimit`: the smallest allowed gap between lines before
+    /// `\lineskip` is used instead.
+    pub line_skip_limit: Fixed,
+}
+
+/// The parameters that relax TeX's line breaking when a paragraph is hard
+/// to set.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LinebreakRelaxation {
+    /// `\emergencystretch`: extra stretchability assumed in each line on a
+    /// third pass, if no feasible breaks were found otherwise.
+    pub emergency_stretch: Fixed,
+
+    /// `\looseness`: how many lines longer (or, if negative, shorter) than
+    /// optimal the paragraph should be made, if possible.
+    pub looseness: i32,
+}
+
+/// The date and time at which a format was built, as recorded by `\year`,
+/// `\month`, `\day`, and `\time`.
+///
+/// The engine sets these parameters when it starts up, and they're saved
+/// along with the rest of the table of equivalents when the format is dumped.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BuildTime {
+    /// `\year`: the year, e.g. 2021.
+    pub year: i32,
+
+    /// `\month`: the month, from 1 to 12.
+    pub month: i32,
+
+    /// `\day`: the day of the month, from 1 to 31.
+    pub day: i32,
+
+    /// `\time`: the number of minutes since midnight.
+    pub time: i32,
+}
+
+impl BuildTime {
+    /// Get the hour of the day, from 0 to 23.
+    pub fn hour(&self) -> i32 {
+        self.time / 60
+    }
+
+    /// Get the minute within the hour, from 0 to 59.
+    pub fn minute(&self) -> i32 {
+        self.time % 60
+    }
+}
+
+/// Formats the time in ISO 8601 style, e.g. `2021-06-01 13:45`.
+impl fmt::Display for BuildTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour(),
+            self.minute()
+        )
+    }
+}
+
+/// The configuration of the output routine, which assembles pages.
+///
+/// The current `\deadcycles` count isn't included, because it's only
+/// meaningful while a job is running, and isn't saved in formats.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct OutputRoutineInfo {
+    /// `\output`: the tokens of the output routine. As in TeX, these include
+    /// the braces that enclose the routine. The list is empty if no routine
+    /// is set, in which case TeX ships out pages unchanged.
+    pub output: Vec<Token>,
+
+    /// `\maxdeadcycles`: the number of times in a row that the output
+    /// routine may run without shipping out a page before TeX complains.
+    pub max_dead_cycles: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_time() {
+        let t = BuildTime {
+            year: 2021,
+            month: 6,
+            day: 1,
+            time: 13 * 60 + 5,
+        };
+
+        assert_eq!(t.hour(), 13);
+        assert_eq!(t.minute(), 5);
+        assert_eq!(t.to_string(), "2021-06-01 13:05");
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2021 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Compact snapshots of the introspectable data in a format.
+//!
+//! A [`Snapshot`] holds the parts of a format that are useful for
+//! introspection — the string table, category codes, control sequence
+//! meanings, and fonts — without the memory arrays, hyphenation trie, and
+//! other internals. Snapshots can be saved in a simple binary encoding
+//! defined by this crate, which is much smaller than the original format and
+//! much faster to load than a full parse.
+//!
+//! The encoding starts with a magic number and [`SNAPSHOT_VERSION`]. Snapshots
+//! written with a different version are rejected when imported, rather than
+//! being misread.
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+use tectonic_errors::prelude::*;
+
+use crate::{base::FormatEndian, catcodes::CatCode, fixed::Fixed, FormatVersion};
+
+/// The magic bytes that start every snapshot.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"XTFSNAP\0";
+
+/// The version of the snapshot encoding written by this crate.
+///
+/// This is incremented whenever the encoding changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A font recorded in a snapshot.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SnapshotFont {
+    /// The font's name.
+    pub name: String,
+
+    /// The font's file "area" (directory).
+    pub area: String,
+
+    /// The "at" size of the font.
+    pub size: Fixed,
+
+    /// The design size of the font.
+    pub design_size: Fixed,
+}
+
+/// The introspectable data extracted from a format.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Snapshot {
+    /// The version of the format that the snapshot was taken from.
+    pub format_version: FormatVersion,
+
+    /// The strings of the format's string table, in order.
+    pub strings: Vec<String>,
+
+    /// The characters whose category codes differ from their initial values
+    /// (see [`CatCode::initial_for`]), in order. All other characters have
+    /// their initial category codes.
+    pub catcodes: Vec<(char, CatCode)>,
+
+    /// The defined control sequences, as pairs of names (without a leading
+    /// backslash) and descriptions of their meanings.
+    pub control_sequences: Vec<(String, String)>,
+
+    /// The defined active characters and descriptions of their meanings.
+    pub actives: Vec<(char, String)>,
+
+    /// The fonts, indexed by font number.
+    pub fonts: Vec<SnapshotFont>,
+}
+
+impl Snapshot {
+    /// Write this snapshot to *stream* in the crate's binary encoding.
+    pub fn export_snapshot<W: Write>(&self, stream: &mut W) -> Result<()> {
+        stream.write_all(SNAPSHOT_MAGIC)?;
+        stream.write_u32::<FormatEndian>(SNAPSHOT_VERSION)?;
+        write_len(stream, self.format_version)?;
+
+        write_len(stream, self.strings.len())?;
+        for s in &self.strings {
+            write_str(stream, s)?;
+        }
+
+        write_len(stream, self.catcodes.len())?;
+        for (c, cat) in &self.catcodes {
+            stream.write_u32::<FormatEndian>(*c as u32)?;
+            stream.write_u8(*cat as u8)?;
+        }
+
+        write_len(stream, self.control_sequences.len())?;
+        for (name, meaning) in &self.control_sequences {
+            write_str(stream, name)?;
+            write_str(stream, meaning)?;
+        }
+
+        write_len(stream, self.actives.len())?;
+        for (c, meaning) in &self.actives {
+            stream.write_u32::<FormatEndian>(*c as u32)?;
+            write_str(stream, meaning)?;
+        }
+
+        write_len(stream, self.fonts.len())?;
+        for font in &self.fonts {
+            write_str(stream, &font.name)?;
+            write_str(stream, &font.area)?;
+            stream.write_i32::<FormatEndian>(font.size.as_sp())?;
+            stream.write_i32::<FormatEndian>(font.design_size.as_sp())?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`Snapshot::export_snapshot`].
+    ///
+    /// Snapshots written with a different [`SNAPSHOT_VERSION`] are rejected.
+    pub fn import_snapshot<R: Read>(stream: &mut R) -> Result<Self> {
+        let mut magic = [0; 8];
+        stream.read_exact(&mut magic)?;
+        ensure!(&magic == SNAPSHOT_MAGIC, "data are not a format snapshot");
+
+        let version = stream.read_u32::<FormatEndian>()?;
+        ensure!(
+            version == SNAPSHOT_VERSION,
+            "unsupported snapshot version {} (expected {})",
+            version,
+            SNAPSHOT_VERSION
+        );
+
+        let format_version = read_len(stream)?;
+
+        let mut strings = Vec::new();
+        for _ in 0..read_len(stream)? {
+            strings.push(read_str(stream)?);
+        }
+
+        let mut catcodes = Vec::new();
+        for _ in 0..read_len(stream)? {
+            let c = read_char(stream)?;
+            let cat = CatCode::from_i32(stream.read_u8()? as i32)?;
+            catcodes.push((c, cat));
+        }
+
+        let mut control_sequences = Vec::new();
+        for _ in 0..read_len(stream)? {
+            let name = read_str(stream)?;
+            let meaning = read_str(stream)?;
+            control_sequences.push((name, meaning));
+        }
+
+        let mut actives = Vec::new();
+        for _ in 0..read_len(stream)? {
+            let c = read_char(stream)?;
+            let meaning = read_str(stream)?;
+            actives.push((c, meaning));
+        }
+
+        let mut fonts = Vec::new();
+        for _ in 0..read_len(stream)? {
+            let name = read_str(stream)?;
+            let area = read_str(stream)?;
+            let size = Fixed(stream.read_i32::<FormatEndian>()?);
+            let design_size = Fixed(stream.read_i32::<FormatEndian>()?);
+            fonts.push(SnapshotFont {
+                name,
+                area,
+                size,
+                design_size,
+            });
+        }
+
+        Ok(Snapshot {
+            format_version,
+            strings,
+            catcodes,
+            control_sequences,
+            actives,
+            fonts,
+        })
+    }
+}
+
+fn write_len<W: Write>(stream: &mut W, len: usize) -> Result<()> {
+    ensure!(len <= u32::MAX as usize, "snapshot item too large");
+    stream.write_u32::<FormatEndian>(len as u32)?;
+    Ok(())
+}
+
+fn write_str<W: Write>(stream: &mut W, s: &str) -> Result<()> {
+    write_len(stream, s.len())?;
+    stream.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn read_len<R: Read>(stream: &mut R) -> Result<usize> {
+    Ok(stream.read_u32::<FormatEndian>()? as usize)
+}
+
+fn read_str<R: Read>(stream: &mut R) -> Result<String> {
+    let len = read_len(stream)?;
+    let mut buf = Vec::new();
+    stream.take(len as u64).read_to_end(&mut buf)?;
+    ensure!(buf.len() == len, "snapshot ended unexpectedly");
+    Ok(String::from_utf8(buf)?)
+}
+
+fn read_char<R: Read>(stream: &mut R) -> Result<char> {
+    let n = stream.read_u32::<FormatEndian>()?;
+    char::from_u32(n).ok_or_else(|| anyhow!("invalid character {:#x} in snapshot", n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let snap = Snapshot {
+            format_version: 32,
+            strings: vec!["relax".to_owned(), "ünïcödé".to_owned()],
+            catcodes: vec![('{', CatCode::BeginGroup), ('~', CatCode::Active)],
+            control_sequences: vec![("relax".to_owned(), "\\relax".to_owned())],
+            actives: vec![('~', "macro:->\\nobreakspace {}".to_owned())],
+            fonts: vec![SnapshotFont {
+                name: "cmr10".to_owned(),
+                area: String::new(),
+                size: Fixed(10 << 16),
+                design_size: Fixed(10 << 16),
+            }],
+        };
+
+        let mut data = Vec::new();
+        snap.export_snapshot(&mut data).unwrap();
+        assert_eq!(Snapshot::import_snapshot(&mut &data[..]).unwrap(), snap);
+
+        // Truncated data should be rejected, not misread.
+        data.pop();
+        assert!(Snapshot::import_snapshot(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut data = Vec::new();
+        Snapshot::default().export_snapshot(&mut data).unwrap();
+        data[11] += 1;
+
+        let err = Snapshot::import_snapshot(&mut &data[..]).unwrap_err();
+        assert!(err.to_string().contains("unsupported snapshot version"));
+    }
+}
@@ -3,19 +3,28 @@
 
 //! Dealing with the TeX string table.
 
-use nom::{
-    multi::count,
-    number::complete::{be_i32, be_u16},
-    Err as NomErr, IResult,
-};
+use nom::{bytes::complete::take, number::complete::be_u16, Err as NomErr};
 
-use crate::parseutils;
+use crate::parseutils::{self, ParseResult};
 
 pub type StrPointer = i32;
 
-#[derive(Debug)]
+/// The smallest string pointer that refers to the string table.
+///
+/// Smaller values are character codes, which TeX treats as single-character
+/// strings. The string with pointer `TOO_BIG_CHAR + n` is element *n* of
+/// [`StringTable::strings`].
+pub const TOO_BIG_CHAR: StrPointer = 0x0001_0000;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StringTable {
     pub strings: Vec<String>,
+
+    /// The raw string pool, in UTF-16 code units.
+    pool: Vec<u16>,
+
+    /// The offset of each string in the pool.
+    starts: Vec<usize>,
 }
 
 pub(crate) fn len_utf16(s: &str) -> usize {
@@ -37,16 +46,57 @@ impl StringTable {
     }
 
     pub fn all_sps(&self) -> impl Iterator<Item = StrPointer> {
-        0x10000..0x10000 + self.strings.len() as i32
+        TOO_BIG_CHAR..TOO_BIG_CHAR + self.strings.len() as i32
+    }
+
+    /// Iterate over all of the strings along with their pointers, in pointer
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (StrPointer, &str)> {
+        self.all_sps().zip(self.strings.iter().map(|s| s.as_ref()))
     }
 
     pub fn lookup(&self, sp: StrPointer) -> &str {
-        assert!(sp > 0xFFFF);
-        &self.strings[sp as usize - 0x10000]
+        assert!(sp >= TOO_BIG_CHAR);
+        &self.strings[(sp - TOO_BIG_CHAR) as usize]
+    }
+
+    /// Get all of the strings, longest first.
+    ///
+    /// Lengths are measured in UTF-16 code units, as stored in the format's
+    /// string pool. Strings of equal length are ordered by pointer.
+    pub fn sorted_by_length(&self) -> Vec<(StrPointer, &str)> {
+        let mut v: Vec<_> = self.iter().collect();
+        v.sort_by_key(|(_, s)| std::cmp::Reverse(s.encode_utf16().count()));
+        v
+    }
+
+    /// Get all of the strings, sorted by their values.
+    pub fn sorted_by_value(&self) -> Vec<(StrPointer, &str)> {
+        let mut v: Vec<_> = self.iter().collect();
+        v.sort_by_key(|(_, s)| *s);
+        v
+    }
+
+    /// Get the raw contents of the string pool.
+    ///
+    /// The pool holds the concatenated strings as UTF-16 code units, just as
+    /// they're stored in the format file. This is a low-level interface for
+    /// debugging; [`StringTable::lookup`] is the usual way to get at strings.
+    pub fn pool(&self) -> &[u16] {
+        &self.pool[..]
+    }
+
+    /// Get the offset of each string in the pool.
+    ///
+    /// The string with pointer `sp` starts at element `sp - TOO_BIG_CHAR` of this
+    /// array, and ends where the next string starts, or at the end of the
+    /// pool.
+    pub fn starts(&self) -> &[usize] {
+        &self.starts[..]
     }
 
     pub fn utf16_length(&self, sp: StrPointer) -> usize {
-        if sp > 0xFFFF {
+        if sp >= TOO_BIG_CHAR {
             len_utf16(self.lookup(sp))
         } else if (32..127).contains(&sp) {
             1
@@ -59,17 +109,23 @@ impl StringTable {
         }
     }
 
-    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], StringTable> {
-        const TOO_BIG_CHAR: i32 = 0x0001_0000;
-
-        let (input, pool_ptr) = be_i32(input)?;
-        let (input, str_ptr) = be_i32(input)?;
+    pub(crate) fn parse(input: &[u8]) -> ParseResult<'_, StringTable> {
+        let (input, pool_ptr) = parseutils::ranged_be_i32(0, i32::MAX)(input)?;
+        let (input, str_ptr) = parseutils::ranged_be_i32(TOO_BIG_CHAR - 1, i32::MAX)(input)?;
         let n_strings = str_ptr - TOO_BIG_CHAR + 1;
 
-        let (input, str_starts) =
-            count(parseutils::ranged_be_i32(0, pool_ptr), n_strings as usize)(input)?;
+        let mut str_starts = Vec::new();
+        let mut prev_start = 0;
+        let mut input = input;
+
+        for _ in 0..n_strings {
+            let (ii, s) = parseutils::ranged_be_i32(prev_start, pool_ptr)(input)?;
+            str_starts.push(s);
+            prev_start = s;
+            input = ii;
+        }
 
-        let (input, str_pool) = count(be_u16, pool_ptr as usize)(input)?;
+        let (input, str_pool) = parseutils::sized_count(be_u16, pool_ptr as usize, 2)(input)?;
         let mut strings = Vec::new();
 
         for i in 0..(n_strings as usize) {
@@ -81,7 +137,7 @@ impl StringTable {
             };
             let s = String::from_utf16(sl).map_err(|_| {
                 use nom::error::ParseError;
-                NomErr::Error(nom::error::Error::from_error_kind(
+                NomErr::Error(parseutils::Error::from_error_kind(
                     input,
                     nom::error::ErrorKind::Satisfy,
                 ))
@@ -89,6 +145,56 @@ impl StringTable {
             strings.push(s);
         }
 
-        Ok((input, StringTable { strings }))
+        let starts = str_starts.into_iter().map(|s| s as usize).collect();
+
+        Ok((
+            input,
+            StringTable {
+                strings,
+                pool: str_pool,
+                starts,
+            },
+        ))
+    }
+
+    /// Build a table from a list of strings.
+    #[cfg(test)]
+    pub(crate) fn from_strings(strings: Vec<String>) -> Self {
+        let mut pool = Vec::new();
+        let mut starts = Vec::new();
+
+        for s in &strings {
+            starts.push(pool.len());
+            pool.extend(s.encode_utf16());
+        }
+
+        StringTable {
+            strings,
+            pool,
+            starts,
+        }
+    }
+
+    /// Skip over the string table without decoding it.
+    pub(crate) fn skip(input: &[u8]) -> ParseResult<'_, ()> {
+        let (input, pool_ptr) = parseutils::ranged_be_i32(0, i32::MAX)(input)?;
+        let (input, str_ptr) = parseutils::ranged_be_i32(TOO_BIG_CHAR - 1, i32::MAX)(input)?;
+        let n_strings = (str_ptr - TOO_BIG_CHAR + 1) as usize;
+
+        let (input, _) = take(n_strings * 4)(input)?;
+        let (input, _) = take(pool_ptr as usize * 2)(input)?;
+        Ok((input, ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iter() {
+        let table = StringTable::from_strings(vec!["relax".to_owned(), "par".to_owned()]);
+        let items: Vec<_> = table.iter().collect();
+        assert_eq!(items, vec![(0x10000, "relax"), (0x10001, "par")]);
     }
 }
@@ -8,7 +8,7 @@
 
 use crate::{commands::CommandCode, eqtb::EqtbPointer};
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Token {
     Char { cmd: CommandCode, chr: i32 },
     ControlSeq { ptr: EqtbPointer },
@@ -0,0 +1,10 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate tectonic_xetex_format;
+
+use tectonic_xetex_format::format::Format;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Format::parse(data);
+    let _ = Format::fonts_only(data);
+});